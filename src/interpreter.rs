@@ -1,34 +1,127 @@
 use std::{cell::RefCell, rc::Rc};
 
-use crate::types::prelude::*;
+use crate::types::{
+    prelude::*,
+    visit::{Idents, Visitor},
+};
 
-pub fn interpret(src: &Syntax) -> SResult<Pointer> {
-    inner_interpret(src, rc_mut_new(State::new()))
+/// Why some code stopped before it finished. It keeps unwinding until something handles it.
+#[derive(Debug)]
+pub enum Unwind {
+    /// `return` was called, so everything stops until the nearest function, which returns this value
+    Return(Pointer),
+    Error(DreamBerdError),
 }
 
-pub fn inner_interpret(src: &Syntax, state: RcMut<State>) -> SResult<Pointer> {
+impl From<DreamBerdError> for Unwind {
+    fn from(err: DreamBerdError) -> Self {
+        Self::Error(err)
+    }
+}
+
+pub type Flow<T> = Result<T, Unwind>;
+
+pub fn interpret(src: &Syntax, config: Config) -> SResult<Pointer> {
+    let state = rc_mut_new(State::with_config(config));
+    let scopes = state.borrow().scopes();
+    let result = run(src, state);
+    // the program is done, so only what it gave back can still use any of its functions
+    scopes.borrow_mut().collect();
+    // `return` outside of a function ends the program
+    match result {
+        Ok(val) | Err(Unwind::Return(val)) => Ok(val),
+        Err(Unwind::Error(err)) => Err(err),
+    }
+}
+
+/// run some code with the engine that's picked in the config
+pub fn run(src: &Syntax, state: RcMut<State>) -> Flow<Pointer> {
+    let engine = state.borrow().config.engine;
+    match engine {
+        Engine::Tree => inner_interpret(src, state),
+        Engine::Vm => crate::vm::Vm::default().run(src, state),
+    }
+}
+
+/// The part of running code that depends on the engine. The tree-walking interpreter runs [`Syntax`] as it is,
+/// and the [`vm`](crate::vm) compiles it first; what the code does once it's running is shared between them.
+pub trait Evaluator {
+    /// an argument of a call, the way it's kept until the function decides whether to run it
+    type Arg;
+
+    /// the syntax that an argument came from, for keywords that look at their arguments instead of running them
+    fn syntax(arg: &Self::Arg) -> &Syntax;
+
+    fn eval(&self, arg: &Self::Arg, state: RcMut<State>) -> Flow<Pointer>;
+
+    /// run only the value of an argument like `b = 3`, which is the argument for the parameter `b`
+    fn eval_named(&self, arg: &Self::Arg, state: RcMut<State>) -> Flow<Pointer>;
+
+    /// run an argument whose value is the value of the whole function, like a branch of an `if` at the end of one
+    fn eval_tail(&self, arg: &Self::Arg, state: RcMut<State>) -> Flow<Tail>;
+
+    /// run code that only exists as syntax, like the body of a class or a `when` listener
+    fn run(&self, syn: &Rc<Syntax>, state: RcMut<State>) -> Flow<Pointer>;
+
+    /// run the body of a user-defined function
+    fn run_body(&self, body: &Rc<Syntax>, state: RcMut<State>) -> Flow<Tail>;
+}
+
+/// Runs the syntax tree directly
+pub struct TreeWalker;
+
+impl Evaluator for TreeWalker {
+    type Arg = Syntax;
+
+    fn syntax(arg: &Syntax) -> &Syntax {
+        arg
+    }
+
+    fn eval(&self, arg: &Syntax, state: RcMut<State>) -> Flow<Pointer> {
+        inner_interpret(arg, state)
+    }
+
+    fn eval_named(&self, arg: &Syntax, state: RcMut<State>) -> Flow<Pointer> {
+        match arg {
+            Syntax::Operation(_, Operation::Equal(1), value, _) => inner_interpret(value, state),
+            other => inner_interpret(other, state),
+        }
+    }
+
+    fn eval_tail(&self, arg: &Syntax, state: RcMut<State>) -> Flow<Tail> {
+        interpret_tail(arg, state)
+    }
+
+    fn run(&self, syn: &Rc<Syntax>, state: RcMut<State>) -> Flow<Pointer> {
+        inner_interpret(syn, state)
+    }
+
+    fn run_body(&self, body: &Rc<Syntax>, state: RcMut<State>) -> Flow<Tail> {
+        interpret_tail(body, state)
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+pub fn inner_interpret(src: &Syntax, state: RcMut<State>) -> Flow<Pointer> {
     match src {
-        Syntax::Statement(false, content, _) => {
-            inner_interpret(content, state.clone())?;
-            Ok(state.borrow().undefined.clone())
+        Syntax::Statement(false, content, ..) => {
+            let evaluated = inner_interpret(content, state.clone())?;
+            end_statement(matches!(**content, Syntax::Ident(..)), &evaluated, &state)
         }
-        Syntax::Statement(true, content, level) => {
+        Syntax::Statement(true, content, level, _) => {
             if *level >= 3 {
                 println!("{content:?}");
             }
             let evaluated = inner_interpret(content, state)?;
-            if *level >= 2 {
-                println!("{evaluated:?}");
-            } else {
-                println!("{evaluated}");
-            }
+            print_value(&evaluated, *level);
             Ok(evaluated)
         }
-        Syntax::UnaryOperation(UnaryOperation::Negate, content) => {
+        Syntax::UnaryOperation(UnaryOperation::Negate, content, _) => {
             let evaluated = inner_interpret(content, state)?;
             Ok(-evaluated)
         }
-        Syntax::Operation(lhs, op, rhs) => interpret_operation(lhs, *op, rhs, state),
+        Syntax::Operation(lhs, op, rhs, span) => interpret_operation(lhs, *op, rhs, *span, &state),
+        Syntax::Annotated(inner, ..) => inner_interpret(inner, state),
         // Syntax::UnaryOperation(UnaryOperation::Call(args), operand) => {
         //     let func = inner_interpret(operand, state.clone())?;
         //     interpret_function(&func, args, state)
@@ -36,16 +129,12 @@ pub fn inner_interpret(src: &Syntax, state: RcMut<State>) -> SResult<Pointer> {
         Syntax::UnaryOperation(
             unary @ (UnaryOperation::Increment | UnaryOperation::Decrement),
             operand,
+            _,
         ) => {
-            let mut operand_ptr = inner_interpret(operand, state)?;
-            match unary {
-                UnaryOperation::Decrement => operand_ptr -= 1.0.into(),
-                UnaryOperation::Increment => operand_ptr += 1.0.into(),
-                _ => unreachable!(),
-            }
-            Ok(operand_ptr)
+            let operand_ptr = inner_interpret(operand, state)?;
+            Ok(step(operand_ptr, *unary == UnaryOperation::Increment))
         }
-        Syntax::Block(statements) => {
+        Syntax::Block(statements, _) => {
             let state = rc_mut_new(State::from_parent(state));
             let mut iter = statements.iter();
             let Some(last) = iter.next_back() else {
@@ -53,83 +142,256 @@ pub fn inner_interpret(src: &Syntax, state: RcMut<State>) -> SResult<Pointer> {
             };
             for syn in iter {
                 inner_interpret(syn, state.clone())?;
-                state.borrow_mut().tick();
+                tick(&state, syn.span())?;
             }
             let res = inner_interpret(last, state)?;
             Ok(res)
         }
-        Syntax::Declare(var_type, ident, lifetime, value) => {
+        Syntax::Declare(var_type, ident, lifetime, annotation, value, span) => {
             let val = inner_interpret(value, state.clone())?;
-            state
-                .borrow_mut()
-                .insert(ident.clone(), val.convert(*var_type), *lifetime);
+            declare(
+                *var_type,
+                ident,
+                *lifetime,
+                annotation.as_ref(),
+                &val,
+                *span,
+                &state,
+            )?;
             // println!("{state:#?}");
             Ok(state.borrow().undefined.clone())
         }
-        Syntax::String(str) => {
+        Syntax::Destructure(var_type, fields, lifetime, value, _) => {
+            let val = inner_interpret(value, state.clone())?;
+            destructure(*var_type, fields, *lifetime, &val, &state);
+            Ok(state.borrow().undefined.clone())
+        }
+        Syntax::File(name, body, _) => {
+            let file_state = rc_mut_new(state.borrow().for_file(name.as_deref()));
+            end_file(inner_interpret(body, file_state))
+        }
+        Syntax::Export(name, target, _) => {
+            let file = inner_interpret(target, state.clone())?;
+            export(name, &file, &state);
+            Ok(state.borrow().undefined.clone())
+        }
+        Syntax::String(str, _) => {
             let mut string_buf = String::new();
             for segment in str {
                 match segment {
-                    StringSegment::Ident(ident) => {
-                        string_buf.push_str(&state.borrow_mut().get(ident.clone()).to_string());
-                    }
                     StringSegment::String(str) => string_buf.push_str(str),
-                    StringSegment::Escudo(lhs, rhs) => string_buf.push_str(
-                        &inner_interpret(
-                            &Syntax::Operation(
-                                Box::new(Syntax::Ident(lhs.clone())),
-                                Operation::Dot,
-                                Box::new(Syntax::Ident(rhs.clone())),
-                            ),
-                            state.clone(),
-                        )?
-                        .to_string(),
-                    ),
+                    StringSegment::Interpolation(inner) => {
+                        string_buf.push_str(&inner_interpret(inner, state.clone())?.to_string());
+                    }
                 }
             }
             Ok(Pointer::from(string_buf.as_ref()))
         }
-        Syntax::UnaryOperation(UnaryOperation::Call(args), func) => {
+        Syntax::Array(items, _) => {
+            let items = items
+                .iter()
+                .map(|item| Ok(element(&inner_interpret(item, state.clone())?)))
+                .collect::<Flow<_>>()?;
+            Ok(Pointer::from(Value::Array(items)))
+        }
+        Syntax::UnaryOperation(UnaryOperation::Call(args), func, span) => {
             let func = inner_interpret(func, state.clone())?;
-            interpret_function(&func, args, state)
+            call_value(&TreeWalker, &func, args, *span, state)
         }
-        Syntax::Ident(ident) => Ok(state.borrow_mut().get(ident.clone())),
-        Syntax::Function(args, body) => {
-            Ok(Pointer::from(Value::Function(args.clone(), *body.clone())))
+        Syntax::UnaryOperation(UnaryOperation::Rest, _, span) => Err(misplaced_rest(*span).into()),
+        Syntax::Ident(ident, _) => Ok(state.borrow_mut().get(ident.clone())),
+        Syntax::Error(span) => Err(unparsed(*span).into()),
+        Syntax::Function(args, body, _) => Ok(Pointer::from(Value::Function(
+            args.clone(),
+            body.clone(),
+            Closure::capture(&state),
+        ))),
+    }
+}
+
+/// the end of a statement that ends with `!`, whose value is thrown away. `bare` is whether the statement is
+/// only a name, because `return!` on its own returns undefined, just like `return()!`
+pub fn end_statement(bare: bool, evaluated: &Pointer, state: &RcMut<State>) -> Flow<Pointer> {
+    if bare && evaluated.with_ref(|val| *val == Value::Keyword(Keyword::Return)) {
+        return Err(Unwind::Return(state.borrow().undefined.clone()));
+    }
+    Ok(state.borrow().undefined.clone())
+}
+
+/// print the value of a statement that ends with `?`; more `?` print more detail
+pub fn print_value(evaluated: &Pointer, level: u8) {
+    if level >= 2 {
+        println!("{evaluated:?}");
+    } else {
+        println!("{evaluated}");
+    }
+}
+
+/// the end of a statement in a block, which counts down lifetimes and uses up a step
+pub fn tick(state: &RcMut<State>, span: Span) -> SResult<()> {
+    state.borrow_mut().tick();
+    use_step(state, span)
+}
+
+/// use up a step of the budget, for a statement or a call
+fn use_step(state: &RcMut<State>, span: Span) -> SResult<()> {
+    let budget = state.borrow().budget.clone();
+    budget.step().map_err(|err| err.with_span(span))
+}
+
+/// `x++` or `x--`
+pub fn step(mut operand: Pointer, increment: bool) -> Pointer {
+    if increment {
+        operand += 1.0.into();
+    } else {
+        operand -= 1.0.into();
+    }
+    operand
+}
+
+pub fn declare(
+    var_type: VarType,
+    ident: &Rc<str>,
+    lifetime: Lifetime,
+    annotation: Option<&Type>,
+    value: &Pointer,
+    span: Span,
+    state: &RcMut<State>,
+) -> SResult<()> {
+    check_type(ident, annotation, value, span, &state.borrow().config)?;
+    // a function is known by the name it's declared with first
+    let value = value.with_ref(|val| match val {
+        Value::Function(params, body, closure) if closure.name().is_none() => {
+            Pointer::from(Value::Function(
+                params.clone(),
+                body.clone(),
+                closure.clone().named(ident.clone()),
+            ))
         }
+        _ => value.clone(),
+    });
+    state
+        .borrow_mut()
+        .insert(ident.clone(), value.convert(var_type), lifetime);
+    Ok(())
+}
+
+/// declare a variable from each field of `value`
+pub fn destructure(
+    var_type: VarType,
+    fields: &[(Rc<str>, Rc<str>)],
+    lifetime: Lifetime,
+    value: &Pointer,
+    state: &RcMut<State>,
+) {
+    for (field, name) in fields {
+        let field_val = value.dot(&Value::from(field.clone()));
+        state
+            .borrow_mut()
+            .insert(name.clone(), field_val.convert(var_type), lifetime);
     }
 }
 
+/// what a file gives back after it runs; `return` only ends the file it's in
+pub fn end_file(result: Flow<Pointer>) -> Flow<Pointer> {
+    match result {
+        Ok(val) | Err(Unwind::Return(val)) => Ok(val),
+        Err(err) => Err(err),
+    }
+}
+
+/// `export name to file`
+pub fn export(name: &Rc<str>, file: &Pointer, state: &RcMut<State>) {
+    let value = state.borrow_mut().get(name.clone());
+    state
+        .borrow()
+        .export(file.to_string().into(), name.clone(), value);
+}
+
+pub fn misplaced_rest(span: Span) -> DreamBerdError {
+    DreamBerdError::runtime("`...` can only go before the last parameter of a function")
+        .with_span(span)
+}
+
+pub fn unparsed(span: Span) -> DreamBerdError {
+    DreamBerdError::runtime("Can't run code that didn't parse").with_span(span)
+}
+
 fn interpret_operation(
     lhs: &Syntax,
     op: Operation,
     rhs: &Syntax,
-    state: RcMut<State>,
-) -> SResult<Pointer> {
-    let mut lhs_eval = inner_interpret(lhs, state.clone())?;
-    if let (Value::Object(_), Operation::Dot, Syntax::Ident(ident)) =
-        (&*lhs_eval.make_const(), op, rhs)
+    span: Span,
+    state: &RcMut<State>,
+) -> Flow<Pointer> {
+    if let (Operation::Equal(1), Syntax::Operation(target, Operation::Index, index, _)) = (op, lhs)
     {
-        let inner_var = lhs_eval.make_var();
-        let Value::Object(ref mut obj) = inner_var.borrow_mut().value else {
-            panic!("Internal Compiler Error at {}:{}", file!(), line!())
-        };
-        let key = Value::from(ident.clone());
-        if let Some(val) = obj.get(&key) {
-            // println!("{val:?}");
-            return Ok(val.clone());
-        }
-        let ptr = state.borrow().undefined.convert(VarType::VarVar);
-        // println!("{ptr:?}");
-        obj.insert(key, ptr.clone());
-        return Ok(ptr);
+        let target_eval = inner_interpret(target, state.clone())?;
+        let index_eval = inner_interpret(index, state.clone())?;
+        let rhs_eval = inner_interpret(rhs, state.clone())?;
+        return assign_index(
+            &TreeWalker,
+            target,
+            &target_eval,
+            &index_eval,
+            rhs_eval,
+            span,
+        );
+    }
+    let lhs_eval = inner_interpret(lhs, state.clone())?;
+    if let (Operation::Dot, Syntax::Ident(ident, _)) = (op, rhs) {
+        if let Some(field) = field(&lhs_eval, ident, state) {
+            return Ok(field);
+        }
+    }
+    let rhs_eval = inner_interpret(rhs, state.clone())?;
+    if let (Operation::Equal(1), Syntax::Annotated(target, annotation, _)) = (op, lhs) {
+        check_type(
+            &target.to_string(),
+            Some(annotation),
+            &rhs_eval,
+            span,
+            &state.borrow().config,
+        )?;
+    }
+    operate(&TreeWalker, lhs_eval, op, rhs_eval, span)
+}
+
+/// `object.name`, which makes the field if it isn't there yet. Anything that isn't an object gives `None`, and
+/// `name` is evaluated like anything else.
+pub fn field(lhs_eval: &Pointer, ident: &Rc<str>, state: &RcMut<State>) -> Option<Pointer> {
+    if !matches!(*lhs_eval.make_const(), Value::Object(_)) {
+        return None;
     }
-    let rhs_eval = inner_interpret(rhs, state)?;
-    // println!("{lhs:?} op {rhs:?}");
+    let inner_var = lhs_eval.make_var();
+    let Value::Object(ref mut obj) = inner_var.borrow_mut().value else {
+        panic!("Internal Compiler Error at {}:{}", file!(), line!())
+    };
+    let key = Value::from(ident.clone());
+    if let Some(val) = obj.get(&key) {
+        // println!("{val:?}");
+        return Some(val.clone());
+    }
+    let ptr = state.borrow().undefined.convert(VarType::VarVar);
+    // println!("{ptr:?}");
+    obj.insert(key, ptr.clone());
+    Some(ptr)
+}
+
+/// do an operation on two values that have already been evaluated
+pub fn operate<E: Evaluator>(
+    engine: &E,
+    mut lhs_eval: Pointer,
+    op: Operation,
+    rhs_eval: Pointer,
+    span: Span,
+) -> Flow<Pointer> {
     // println!("{lhs_eval:?} op {rhs_eval:?}");
     let ret = match op {
         Operation::Equal(1) => {
-            lhs_eval.assign(&rhs_eval)?;
+            lhs_eval
+                .assign(&rhs_eval)
+                .map_err(|err| err.with_span(span))?;
             rhs_eval
         }
         Operation::Equal(precision) => lhs_eval.eq(&rhs_eval, precision - 1),
@@ -138,7 +400,11 @@ fn interpret_operation(
         Operation::Mul => lhs_eval * rhs_eval,
         Operation::Div => lhs_eval / rhs_eval,
         Operation::Mod => lhs_eval % rhs_eval,
+        Operation::Pow => Pointer::from(lhs_eval.with_refs(&rhs_eval, Value::pow)),
+        Operation::FloorDiv => Pointer::from(lhs_eval.with_refs(&rhs_eval, Value::floor_div)),
+        Operation::In => Pointer::from(lhs_eval.with_refs(&rhs_eval, Value::is_in)),
         Operation::Dot => rhs_eval.with_ref(|rhs_eval| lhs_eval.dot(rhs_eval)),
+        Operation::Index => rhs_eval.with_ref(|rhs_eval| lhs_eval.index(rhs_eval)),
         Operation::And => lhs_eval & rhs_eval,
         Operation::Or => lhs_eval | rhs_eval,
         Operation::AddEq => {
@@ -161,6 +427,14 @@ fn interpret_operation(
             lhs_eval %= rhs_eval;
             lhs_eval
         }
+        Operation::PowEq => {
+            lhs_eval.set_inner(lhs_eval.with_refs(&rhs_eval, Value::pow));
+            lhs_eval
+        }
+        Operation::FloorDivEq => {
+            lhs_eval.set_inner(lhs_eval.with_refs(&rhs_eval, Value::floor_div));
+            lhs_eval
+        }
         Operation::Lt => Pointer::from(lhs_eval < rhs_eval),
         Operation::Le => Pointer::from(lhs_eval <= rhs_eval),
         Operation::Gt => Pointer::from(lhs_eval > rhs_eval),
@@ -174,18 +448,81 @@ fn interpret_operation(
         | Operation::Equal(1)
         | Operation::MulEq
         | Operation::DivEq
-        | Operation::ModEq,
+        | Operation::ModEq
+        | Operation::PowEq
+        | Operation::FloorDivEq,
     ) = (ret.as_var(), op)
     {
-        update_pointer(&val)?;
+        update_pointer(engine, &val, span)?;
     }
     Ok(ret)
 }
 
-fn update_pointer(val: &RefCell<MutValue>) -> SResult<()> {
+/// `target[index] = rhs`. Arrays are changed in place; anything else is assigned to like `target.index = rhs`
+pub fn assign_index<E: Evaluator>(
+    engine: &E,
+    target: &Syntax,
+    target_eval: &Pointer,
+    index_eval: &Pointer,
+    rhs_eval: Pointer,
+    span: Span,
+) -> Flow<Pointer> {
+    let index_eval = index_eval.clone_inner();
+    let (Value::Array(mut items), &Value::Number(idx)) = (target_eval.clone_inner(), &index_eval)
+    else {
+        target_eval
+            .index(&index_eval)
+            .assign(&rhs_eval)
+            .map_err(|err| err.with_span(span))?;
+        return Ok(rhs_eval);
+    };
+    let Some(var) = target_eval.as_var() else {
+        return Err(DreamBerdError::assignment(format!(
+            "Can't change an element of `{target}`, because it's a constant array"
+        ))
+        .with_span(span)
+        .into());
+    };
+    Value::set_element(&mut items, idx, element(&rhs_eval)).map_err(|err| err.with_span(span))?;
+    var.borrow_mut().assign(Value::Array(items));
+    update_pointer(engine, &var, span)?;
+    Ok(rhs_eval)
+}
+
+/// arrays hold their own copies of values, so changing a variable doesn't change an array it was put in
+pub fn element(value: &Pointer) -> Pointer {
+    Pointer::ConstConst(value.make_const())
+}
+
+/// with `--strict-types`, make sure a value fits the type annotation on the name it's being bound to
+pub fn check_type(
+    name: &str,
+    annotation: Option<&Type>,
+    value: &Pointer,
+    span: Span,
+    config: &Config,
+) -> SResult<()> {
+    let Some(annotation) = annotation else {
+        return Ok(());
+    };
+    if !config.strict_types || value.with_ref(|value| annotation.accepts(value)) {
+        return Ok(());
+    }
+    let got = value.with_ref(|value| match value {
+        // numbers can be the right type but out of range, like `300` for a `u8`
+        Value::Number(num) => format!("`{num}`"),
+        other => format!("a value of type `{}`", other.type_name()),
+    });
+    Err(DreamBerdError::runtime(format!(
+        "`{name}` is declared as `{annotation}`, but got {got}"
+    ))
+    .with_span(span))
+}
+
+fn update_pointer<E: Evaluator>(engine: &E, val: &RefCell<MutValue>, span: Span) -> Flow<()> {
     let listeners = val.borrow().event_listeners.clone();
     for (listener, state) in listeners {
-        inner_interpret(&listener, state)?;
+        engine.run(&listener, state)?;
     }
     let next_handles = val.borrow_mut().flush_next_handles();
     let new_value = val.borrow().value.clone();
@@ -195,46 +532,69 @@ fn update_pointer(val: &RefCell<MutValue>) -> SResult<()> {
                 // println!("{handle_var:?}");
                 handle_var.borrow_mut().assign(new_value.clone());
                 // println!("{handle_var:?}");
-                update_pointer(&handle_var)?;
+                update_pointer(engine, &handle_var, span)?;
+            }
+            None => {
+                return Err(
+                    DreamBerdError::assignment("Can't assign to a constant value")
+                        .with_span(span)
+                        .into(),
+                )
             }
-            None => return Err(String::from("Can't assign to a constant value")),
         }
     }
     Ok(())
 }
 
-#[allow(clippy::too_many_lines)]
-fn interpret_function(func: &Pointer, args: &[Syntax], state: RcMut<State>) -> SResult<Pointer> {
+/// the syntax of every argument, for error messages
+fn arg_syntax<E: Evaluator>(args: &[E::Arg]) -> Vec<&Syntax> {
+    args.iter().map(E::syntax).collect()
+}
+
+/// Call a value with some arguments. Keywords decide which of their arguments get run, and when.
+#[allow(
+    clippy::too_many_lines,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+pub fn call_value<E: Evaluator>(
+    engine: &E,
+    func: &Pointer,
+    args: &[E::Arg],
+    span: Span,
+    state: RcMut<State>,
+) -> Flow<Pointer> {
+    use_step(&state, span)?;
     func.with_ref(|func_eval| match func_eval {
         Value::Keyword(Keyword::If) => {
             let [condition, body, ..] = args else {
-                return Err(String::from(
+                return Err(DreamBerdError::arity(
                     "If statement requires two arguments: condition and body",
-                ));
+                )
+                .with_span(span)
+                .into());
             };
-            let condition_evaluated = inner_interpret(condition, state.clone())?;
-            // println!("{condition_evaluated:?}");
-            let bool = condition_evaluated.with_ref(Value::bool);
-            if bool == Boolean::True {
-                inner_interpret(body, state)
-            } else if let (Boolean::Maybe, Some(body)) = (bool, args.get(3)) {
-                inner_interpret(body, state)
-            } else {
-                match args.get(2) {
-                    Some(else_statement) => inner_interpret(else_statement, state),
-                    None => Ok(state.borrow().undefined.clone()),
-                }
+            match if_branch(engine, condition, body, args, &state)? {
+                Some(branch) => engine.eval(branch, state),
+                None => Ok(state.borrow().undefined.clone()),
             }
         }
         Value::Keyword(Keyword::Delete) => {
-            if let [Syntax::Ident(key)] = args {
-                state.borrow_mut().delete(key.clone());
+            if let [arg] = args {
+                if let Syntax::Ident(key, _) = E::syntax(arg) {
+                    state.borrow_mut().delete(key.clone());
+                }
             }
             Ok(state.borrow().undefined.clone())
         }
         Value::Keyword(Keyword::Forget) => {
-            let [Syntax::Ident(ident)] = args else {
-                return Err(String::from("`forget` keyword requires one argument"));
+            let [Syntax::Ident(ident, _)] = arg_syntax::<E>(args)[..] else {
+                return Err(
+                    DreamBerdError::arity("`forget` keyword requires one argument")
+                        .with_span(span)
+                        .into(),
+                );
             };
             let undefined = state.borrow().undefined.clone();
             state
@@ -244,9 +604,13 @@ fn interpret_function(func: &Pointer, args: &[Syntax], state: RcMut<State>) -> S
         }
         Value::Keyword(Keyword::Previous) => {
             let [arg] = args else {
-                return Err(String::from("`previous` keyword requires one argument"));
+                return Err(
+                    DreamBerdError::arity("`previous` keyword requires one argument")
+                        .with_span(span)
+                        .into(),
+                );
             };
-            let evaluated = inner_interpret(arg, state.clone())?;
+            let evaluated = engine.eval(arg, state.clone())?;
             match evaluated.as_var() {
                 Some(eval) => Ok(eval.borrow().previous.as_ref().map_or_else(
                     move || state.borrow().undefined.clone(),
@@ -257,18 +621,32 @@ fn interpret_function(func: &Pointer, args: &[Syntax], state: RcMut<State>) -> S
         }
         Value::Keyword(Keyword::Current) => {
             let [arg] = args else {
-                return Err(String::from("`current` keyword requires one argument"));
+                return Err(
+                    DreamBerdError::arity("`current` keyword requires one argument")
+                        .with_span(span)
+                        .into(),
+                );
             };
-            inner_interpret(arg, state)
+            engine.eval(arg, state)
         }
         Value::Keyword(Keyword::Next) => {
             let [arg] = args else {
-                return Err(String::from("`next` keyword requires one argument"));
+                return Err(
+                    DreamBerdError::arity("`next` keyword requires one argument")
+                        .with_span(span)
+                        .into(),
+                );
             };
-            let evaluated = inner_interpret(arg, state)?;
+            let evaluated = engine.eval(arg, state)?;
             let next_ptr = Pointer::ConstVar(rc_mut_new(Value::empty_object().into()));
             evaluated.as_var().map_or_else(
-                || Err(String::from("`next` keyword requires a mutable value")),
+                || {
+                    Err(
+                        DreamBerdError::runtime("`next` keyword requires a mutable value")
+                            .with_span(span)
+                            .into(),
+                    )
+                },
                 |eval| {
                     eval.borrow_mut().add_next_handle(next_ptr.clone());
                     // println!("{eval:?}");
@@ -279,55 +657,77 @@ fn interpret_function(func: &Pointer, args: &[Syntax], state: RcMut<State>) -> S
         }
         Value::Keyword(Keyword::When) => {
             let [condition, body] = args else {
-                return Err(String::from(
+                return Err(DreamBerdError::arity(
                     "`when` keyword requires two arguments; condition and body",
-                ));
+                )
+                .with_span(span)
+                .into());
             };
-            let idents = find_idents_in_syntax(condition);
-            for ident in idents {
+            let (condition, body) = (E::syntax(condition), E::syntax(body));
+            let listener = Rc::new(Syntax::UnaryOperation(
+                UnaryOperation::Call(Rc::new([condition.clone(), body.clone()])),
+                Box::new(Syntax::Ident("if".into(), span)),
+                span,
+            ));
+            for ident in find_idents_in_syntax(condition) {
                 let Some(var) = state.borrow_mut().get(ident).as_var() else {
                     continue;
                 };
-                var.borrow_mut().add_event_listener(
-                    Syntax::UnaryOperation(
-                        UnaryOperation::Call(vec![condition.clone(), body.clone()]),
-                        Box::new(Syntax::Ident("if".into())),
-                    ),
-                    state.clone(),
-                );
+                var.borrow_mut()
+                    .add_event_listener(listener.clone(), state.clone());
             }
             Ok(state.borrow().undefined.clone())
         }
         Value::Keyword(Keyword::Function) => {
-            let [Syntax::Ident(name), args, body] = args else {
-                return Err(format!(
-                    "Invalid arguments for `function`: `{args:?}`; expected name, args, and body"
-                ));
+            let [Syntax::Ident(name, _), args, body] = arg_syntax::<E>(args)[..] else {
+                return Err(DreamBerdError::arity(format!(
+                    "Invalid arguments for `function`: `{:?}`; expected name, args, and body",
+                    arg_syntax::<E>(args)
+                ))
+                .with_span(span)
+                .into());
             };
             let args = match args {
-                Syntax::Block(args) => args.clone(),
+                Syntax::Block(args, _) => args.clone(),
                 other => vec![other.clone()],
             };
-            let args: Vec<Rc<str>> = args
+            let args: Vec<Param> = args
                 .into_iter()
-                .map(|syn| match syn {
-                    Syntax::Ident(str) => Ok(str),
-                    other => Err(format!("Invalid parameter name: `{other}`")),
+                .map(|syn| {
+                    Param::from_syntax(&syn).ok_or_else(|| {
+                        DreamBerdError::runtime(format!("Invalid parameter name: `{syn}`"))
+                            .with_span(syn.span())
+                    })
                 })
                 .collect::<Result<_, _>>()?;
-            let inner_val = Value::Function(args, body.clone());
+            if let Some(rest) = Param::misplaced_rest(&args) {
+                return Err(DreamBerdError::runtime(format!(
+                    "`...{}` has to be the last parameter",
+                    rest.name
+                ))
+                .with_span(span)
+                .into());
+            }
+            let inner_val = Value::Function(
+                args,
+                Rc::new(body.clone()),
+                Closure::capture(&state).named(name.clone()),
+            );
             state
                 .borrow_mut()
                 .insert(name.clone(), Pointer::from(inner_val), Lifetime::Default);
             Ok(state.borrow().undefined.clone())
         }
         Value::Keyword(Keyword::Class) => {
-            let [Syntax::Ident(name), Syntax::Block(body)] = args else {
-                return Err(format!(
-                    "Invalid arguments for `class`: `{args:?}`; expected name and body"
-                ));
+            let [Syntax::Ident(name, _), Syntax::Block(body, _)] = arg_syntax::<E>(args)[..] else {
+                return Err(DreamBerdError::arity(format!(
+                    "Invalid arguments for `class`: `{:?}`; expected name and body",
+                    arg_syntax::<E>(args)
+                ))
+                .with_span(span)
+                .into());
             };
-            let inner_value = Value::Class(body.clone());
+            let inner_value = Value::Class(body.iter().cloned().map(Rc::new).collect());
             state.borrow_mut().insert(
                 name.clone(),
                 Pointer::ConstVar(rc_mut_new(inner_value.into())),
@@ -337,74 +737,187 @@ fn interpret_function(func: &Pointer, args: &[Syntax], state: RcMut<State>) -> S
         }
         Value::Keyword(Keyword::New) => {
             let [class] = args else {
-                return Err(format!(
-                    "Invalid arguments for `new`: `{args:?}`; expected a class"
-                ));
+                return Err(DreamBerdError::arity(format!(
+                    "Invalid arguments for `new`: `{:?}`; expected a class",
+                    arg_syntax::<E>(args)
+                ))
+                .with_span(span)
+                .into());
             };
-            let class_pointer = inner_interpret(class, state.clone())?;
+            let class_pointer = engine.eval(class, state.clone())?;
             let Some(class_ref) = class_pointer.as_var() else {
-                return Err(format!(
+                return Err(DreamBerdError::runtime(format!(
                     "Expected a mutable reference to a class; got `{class_pointer:?}`"
-                ));
+                ))
+                .with_span(span)
+                .into());
             };
             class_ref.borrow_mut().assign(Value::empty_object());
             let Some(Value::Class(class_body)) = class_ref.borrow().previous.clone() else {
-                return Err(format!(
+                return Err(DreamBerdError::runtime(format!(
                     "Expected a mutable reference to a class; got `{:?}`",
                     class_ref.borrow()
-                ));
+                ))
+                .with_span(span)
+                .into());
             };
             let inner_state = rc_mut_new(State::from_parent(state));
-            for statement in class_body {
-                inner_interpret(&statement, inner_state.clone())?;
+            for statement in &class_body {
+                engine.run(statement, inner_state.clone())?;
             }
             let inner_obj = inner_state.borrow().locals_to_object();
             Ok(Pointer::from(Value::Object(inner_obj)))
         }
         Value::Keyword(Keyword::Eval) => {
             let [body] = args else {
-                return Err(format!(
-                    "You can only `eval` one thing at a time; got `{args:?}`"
-                ));
+                return Err(DreamBerdError::arity(format!(
+                    "You can only `eval` one thing at a time; got `{:?}`",
+                    arg_syntax::<E>(args)
+                ))
+                .with_span(span)
+                .into());
             };
-            let text = inner_interpret(body, state.clone())?.to_string();
+            let text = engine.eval(body, state.clone())?.to_string();
             // #[cfg(debug_assertions)]
             // println!("Evaluating Inner: {text}");
-            let tokens = crate::lexer::tokenize(&text)?;
-            // #[cfg(debug_assertions)]
-            // println!("Evaluating Tokens: {tokens:?}");
-            let syntax = crate::parser::parse(tokens)?;
-            // #[cfg(debug_assertions)]
-            // println!("Evaluating Syntax: {syntax:?}");
-            inner_interpret(&syntax, state)
+            let evaluated = crate::lexer::tokenize(&text)
+                // .inspect(|tokens| println!("Evaluating Tokens: {tokens:?}"))
+                .and_then(crate::parser::parse)
+                // .inspect(|syntax| println!("Evaluating Syntax: {syntax:?}"))
+                .map_err(Unwind::from)
+                .and_then(|syntax| engine.run(&Rc::new(syntax), state));
+            // errors inside the evaluated code are reported at the call to `eval`
+            evaluated.map_err(|unwind| match unwind {
+                Unwind::Error(mut err) => Unwind::Error(match err.span.replace(span) {
+                    Some(inner) => err.with_note(format!(
+                        "at {inner} in the code evaluated by `eval`: `{text}`"
+                    )),
+                    None => err,
+                }),
+                ret @ Unwind::Return(_) => ret,
+            })
+        }
+        Value::Keyword(Keyword::Len) => {
+            let [arg] = args else {
+                return Err(DreamBerdError::arity("`len` keyword requires one argument")
+                    .with_span(span)
+                    .into());
+            };
+            engine.eval(arg, state)?.with_ref(|value| match value {
+                Value::Array(items) => Ok(Pointer::from(items.len() as f64)),
+                Value::String(str) => Ok(Pointer::from(str.chars().count() as f64)),
+                other => Err(DreamBerdError::runtime(format!(
+                    "`len` requires an array or a string; got a value of type `{}`",
+                    other.type_name()
+                ))
+                .with_span(span)
+                .into()),
+            })
+        }
+        Value::Keyword(keyword @ (Keyword::Push | Keyword::Pop)) => {
+            let (array, item) = match (keyword, args) {
+                (Keyword::Push, [array, item]) => (array, Some(item)),
+                (Keyword::Pop, [array]) => (array, None),
+                _ => {
+                    return Err(DreamBerdError::arity(format!(
+                        "`{keyword}` keyword requires {}",
+                        if *keyword == Keyword::Push {
+                            "two arguments: array and item"
+                        } else {
+                            "one argument"
+                        }
+                    ))
+                    .with_span(span)
+                    .into())
+                }
+            };
+            let array_ptr = engine.eval(array, state.clone())?;
+            let Value::Array(mut items) = array_ptr.clone_inner() else {
+                return Err(DreamBerdError::runtime(format!(
+                    "`{keyword}` requires an array; got a value of type `{}`",
+                    array_ptr.with_ref(Value::type_name)
+                ))
+                .with_span(span)
+                .into());
+            };
+            let Some(var) = array_ptr.as_var() else {
+                return Err(DreamBerdError::assignment(format!(
+                    "Can't `{keyword}` on `{}`, because it's a constant array",
+                    E::syntax(array)
+                ))
+                .with_span(span)
+                .into());
+            };
+            let popped = match item {
+                Some(item) => {
+                    items.push(element(&engine.eval(item, state.clone())?));
+                    None
+                }
+                None => items.pop(),
+            };
+            var.borrow_mut().assign(Value::Array(items));
+            update_pointer(engine, &var, span)?;
+            Ok(popped.unwrap_or_else(|| state.borrow().undefined.clone()))
+        }
+        Value::Keyword(Keyword::Stack) => {
+            if !args.is_empty() {
+                return Err(
+                    DreamBerdError::arity("`stack` keyword doesn't take any arguments")
+                        .with_span(span)
+                        .into(),
+                );
+            }
+            let frames = state
+                .borrow()
+                .calls
+                .borrow()
+                .iter()
+                .rev()
+                .map(|frame| Pointer::from(frame.to_string().as_str()))
+                .collect();
+            Ok(Pointer::from(Value::Array(frames)))
+        }
+        Value::Keyword(Keyword::Return) => {
+            let val = match args {
+                [] => state.borrow().undefined.clone(),
+                [val] => engine.eval(val, state)?,
+                _ => {
+                    return Err(DreamBerdError::arity("You can only `return` one value")
+                        .with_span(span)
+                        .into())
+                }
+            };
+            Err(Unwind::Return(val))
         }
         Value::Object(obj) => {
             let Some(call) = obj.get(&"call".into()) else {
-                return Err(format!("`Object({obj:?})` is not a function"));
+                return Err(DreamBerdError::runtime(format!(
+                    "`Object({obj:?})` is not a function"
+                ))
+                .with_span(span)
+                .into());
             };
             let mut new_state = State::from_parent(state);
             new_state.insert("self".into(), func.clone(), Lifetime::Default);
-            interpret_function(call, args, rc_mut_new(new_state))
+            call_value(engine, call, args, span, rc_mut_new(new_state))
         }
-        Value::Function(fn_args, body) => {
-            let mut inner_state = State::from_parent(state.clone());
-            for (idx, ident) in fn_args.iter().enumerate() {
-                let arg_eval = if let Some(syn) = args.get(idx) {
-                    inner_interpret(syn, state.clone())?
-                } else {
-                    state.borrow().undefined.clone()
-                };
-                inner_state.insert(ident.clone(), arg_eval, Lifetime::Default);
-            }
-            inner_interpret(body, rc_mut_new(inner_state))
+        Value::Function(fn_args, ..) => {
+            let call = TailCall::new(engine, func, fn_args, args, span, &state, state.clone())?;
+            call_function(engine, call, &state)
         }
         Value::String(str) => {
             let [arg] = args else {
-                return Err("indexing string requires one argument".to_string());
+                return Err(
+                    DreamBerdError::arity("indexing string requires one argument")
+                        .with_span(span)
+                        .into(),
+                );
             };
-            let rhs = inner_interpret(arg, state.clone())?;
+            let rhs = engine.eval(arg, state.clone())?;
             let Value::Number(rhs) = rhs.clone_inner() else {
-                return Err("indexing string requires number".to_string());
+                return Err(DreamBerdError::runtime("indexing string requires number")
+                    .with_span(span)
+                    .into());
             };
             let rhs = rhs as usize;
             let char = str.chars().nth(rhs);
@@ -415,28 +928,339 @@ fn interpret_function(func: &Pointer, args: &[Syntax], state: RcMut<State>) -> S
         }
         other => {
             let [arg] = args else {
-                return Err(format!("`{other}` is not a function"));
+                return Err(
+                    DreamBerdError::runtime(format!("`{other}` is not a function"))
+                        .with_span(span)
+                        .into(),
+                );
             };
-            let rhs = inner_interpret(arg, state)?;
+            let rhs = engine.eval(arg, state)?;
             Ok((other.clone() * rhs.clone_inner()).into())
         }
     })
 }
 
-fn find_idents_in_syntax(syn: &Syntax) -> Vec<Rc<str>> {
-    match syn {
-        Syntax::Ident(id) => vec![id.clone()],
-        Syntax::Block(stmts) => stmts.iter().flat_map(find_idents_in_syntax).collect(),
-        Syntax::UnaryOperation(UnaryOperation::Call(args), func) => args
+/// pick which branch of `if(condition, body, else, maybe)` to run, if any
+fn if_branch<'a, E: Evaluator>(
+    engine: &E,
+    condition: &E::Arg,
+    body: &'a E::Arg,
+    args: &'a [E::Arg],
+    state: &RcMut<State>,
+) -> Flow<Option<&'a E::Arg>> {
+    let condition_evaluated = engine.eval(condition, state.clone())?;
+    // println!("{condition_evaluated:?}");
+    let bool = condition_evaluated.with_ref(Value::bool);
+    Ok(match (bool, args.get(3)) {
+        (Boolean::True, _) => Some(body),
+        (Boolean::Maybe, Some(body)) => Some(body),
+        _ => args.get(2),
+    })
+}
+
+/// A call to a user-defined function, with its arguments already evaluated
+pub struct TailCall {
+    func: Pointer,
+    /// the argument for each parameter, if it was given one
+    args: Vec<Option<(Pointer, Span)>>,
+    /// every argument, in the order they were given
+    arguments: Vec<Pointer>,
+    /// the scope that the function's scope goes inside of, if it isn't a closure
+    scope: RcMut<State>,
+    span: Span,
+    /// the call was a statement, so whatever it returns is thrown away
+    discard: bool,
+}
+
+impl TailCall {
+    /// evaluate the arguments and match them up with the parameters, either in order or by name, like `f(b = 3)`
+    fn new<E: Evaluator>(
+        engine: &E,
+        func: &Pointer,
+        params: &[Param],
+        args: &[E::Arg],
+        span: Span,
+        state: &RcMut<State>,
+        scope: RcMut<State>,
+    ) -> Flow<Self> {
+        let positional = args
             .iter()
-            .chain(std::iter::once(&**func))
-            .flat_map(find_idents_in_syntax)
-            .collect(),
-        Syntax::UnaryOperation(UnaryOperation::Negate, syn) => find_idents_in_syntax(syn),
-        Syntax::Operation(lhs, _, rhs) => find_idents_in_syntax(lhs)
-            .into_iter()
-            .chain(find_idents_in_syntax(rhs))
-            .collect(),
-        _ => Vec::new(),
+            .filter(|arg| named_arg(E::syntax(arg), params).is_none())
+            .count();
+        let has_rest = params.last().is_some_and(|param| param.rest);
+        // the parameters that take one argument each
+        let fixed = params.len() - usize::from(has_rest);
+        if positional > fixed && !has_rest {
+            return Err(DreamBerdError::arity(format!(
+                "This function takes {fixed} arguments, but it was given {positional}"
+            ))
+            .with_span(span)
+            .into());
+        }
+        let mut bound = vec![None; params.len()];
+        let mut arguments = Vec::with_capacity(args.len());
+        let mut rest = Vec::new();
+        let mut rest_span: Option<Span> = None;
+        let mut next_positional = 0;
+        for arg in args {
+            let arg_span = E::syntax(arg).span();
+            let named = named_arg(E::syntax(arg), params);
+            let idx = named.unwrap_or_else(|| {
+                next_positional += 1;
+                next_positional - 1
+            });
+            let evaluate = || {
+                if named.is_some() {
+                    engine.eval_named(arg, state.clone())
+                } else {
+                    engine.eval(arg, state.clone())
+                }
+            };
+            if idx >= fixed {
+                let evaluated = evaluate()?;
+                arguments.push(element(&evaluated));
+                rest.push(element(&evaluated));
+                rest_span = Some(rest_span.map_or(arg_span, |span| span.to(arg_span)));
+                continue;
+            }
+            if bound[idx].is_some() {
+                return Err(DreamBerdError::arity(format!(
+                    "`{}` was given more than one argument",
+                    params[idx].name
+                ))
+                .with_span(arg_span)
+                .into());
+            }
+            let evaluated = evaluate()?;
+            arguments.push(element(&evaluated));
+            bound[idx] = Some((evaluated, arg_span));
+        }
+        if has_rest {
+            bound[fixed] = Some((Pointer::from(Value::Array(rest)), rest_span.unwrap_or(span)));
+        }
+        Ok(Self {
+            func: func.clone(),
+            args: bound,
+            arguments,
+            scope,
+            span,
+            discard: false,
+        })
+    }
+}
+
+impl TailCall {
+    /// the call's place in the call stack
+    fn frame(&self) -> Frame {
+        let name = self.func.with_ref(|func| match func {
+            Value::Function(.., closure) => closure.name(),
+            _ => None,
+        });
+        Frame {
+            name,
+            call_site: self.span,
+        }
+    }
+}
+
+/// Get which parameter an argument like `b = 3` is for. It's only a named argument if there's a
+/// parameter with that name; otherwise it's an assignment.
+fn named_arg(arg: &Syntax, params: &[Param]) -> Option<usize> {
+    let Syntax::Operation(lhs, Operation::Equal(1), _, _) = arg else {
+        return None;
+    };
+    let Syntax::Ident(name, _) = &**lhs else {
+        return None;
+    };
+    params
+        .iter()
+        .position(|param| param.name == *name && !param.rest)
+}
+
+/// What's left to do after running code in tail position
+pub enum Tail {
+    Done(Pointer),
+    Call(TailCall),
+}
+
+impl Tail {
+    /// the code was a statement, so its value is thrown away
+    pub fn discard(self, state: &RcMut<State>) -> Self {
+        match self {
+            Self::Call(call) => Self::Call(TailCall {
+                discard: true,
+                ..call
+            }),
+            Self::Done(_) => Self::Done(state.borrow().undefined.clone()),
+        }
     }
 }
+
+/// Run code whose value is the value of the whole function. A call at the very end isn't made here; it's
+/// handed back so that [`call_function`] can make it without using more stack.
+fn interpret_tail(src: &Syntax, state: RcMut<State>) -> Flow<Tail> {
+    match src {
+        Syntax::Statement(false, content, ..)
+            if matches!(
+                **content,
+                Syntax::UnaryOperation(UnaryOperation::Call(_), ..)
+            ) =>
+        {
+            Ok(interpret_tail(content, state.clone())?.discard(&state))
+        }
+        Syntax::Block(statements, _) => {
+            let state = rc_mut_new(State::from_parent(state));
+            let Some((last, rest)) = statements.split_last() else {
+                return Ok(Tail::Done(state.borrow().undefined.clone()));
+            };
+            for syn in rest {
+                inner_interpret(syn, state.clone())?;
+                tick(&state, syn.span())?;
+            }
+            interpret_tail(last, state)
+        }
+        Syntax::Annotated(inner, ..) => interpret_tail(inner, state),
+        Syntax::UnaryOperation(UnaryOperation::Call(args), func_syntax, span) => {
+            let func = inner_interpret(func_syntax, state.clone())?;
+            tail_call(&TreeWalker, &func, args, *span, &state)
+        }
+        other => inner_interpret(other, state).map(Tail::Done),
+    }
+}
+
+/// Call a value in tail position. A call to a user-defined function isn't made; it's handed back instead.
+pub fn tail_call<E: Evaluator>(
+    engine: &E,
+    func: &Pointer,
+    args: &[E::Arg],
+    span: Span,
+    state: &RcMut<State>,
+) -> Flow<Tail> {
+    func.with_ref(|func_eval| match (func_eval, args) {
+        (Value::Function(params, ..), _) => {
+            use_step(state, span)?;
+            Ok(Tail::Call(TailCall::new(
+                engine,
+                func,
+                params,
+                args,
+                span,
+                state,
+                state.clone(),
+            )?))
+        }
+        (Value::Keyword(Keyword::If), [condition, body, ..]) => {
+            use_step(state, span)?;
+            if_branch(engine, condition, body, args, state)?.map_or_else(
+                || Ok(Tail::Done(state.borrow().undefined.clone())),
+                |branch| engine.eval_tail(branch, state.clone()),
+            )
+        }
+        // this uses up its own step
+        _ => call_value(engine, func, args, span, state.clone()).map(Tail::Done),
+    })
+}
+
+/// how little stack a call can have left before it gets more
+const STACK_RED_ZONE: usize = 512 * 1024;
+/// how much more stack a call gets at once when it's running low
+const STACK_GROWTH: usize = 8 * 1024 * 1024;
+
+/// Call a user-defined function. Calls in tail position reuse this frame, so a function can call
+/// itself forever without running out of stack.
+fn call_function<E: Evaluator>(
+    engine: &E,
+    mut call: TailCall,
+    state: &RcMut<State>,
+) -> Flow<Pointer> {
+    let calls = state.borrow().calls.clone();
+    let max_depth = state.borrow().config.max_depth;
+    if calls.borrow().len() >= max_depth {
+        return Err(DreamBerdError::runtime(format!(
+            "Too many calls at once; the most that can be running is {max_depth}"
+        ))
+        .with_span(call.span)
+        .with_note("use `--max-depth` to allow more, or make the call the last thing its function does, so it doesn't count")
+        .or_stack(&calls.borrow())
+        .into());
+    }
+    // Calls that aren't at the end of a function use up the Rust stack, so more of it is made whenever it's
+    // running low. That way, going past `max_depth` is an error on any thread, instead of a crash.
+    stacker::maybe_grow(STACK_RED_ZONE, STACK_GROWTH, move || {
+        calls.borrow_mut().push(call.frame());
+        // with `--dynamic-scope`, where the first call was made from
+        let base = call.scope.clone();
+        let mut discard = false;
+        let result = loop {
+            discard |= call.discard;
+            let tail = call.func.with_ref(|func| {
+                let Value::Function(params, body, closure) = func else {
+                    return Err(
+                        DreamBerdError::runtime(format!("`{func}` is not a function"))
+                            .with_span(call.span)
+                            .into(),
+                    );
+                };
+                let scope = closure.scope().unwrap_or_else(|| call.scope.clone());
+                let inner_state = rc_mut_new(State::from_parent(scope));
+                inner_state.borrow_mut().insert(
+                    "arguments".into(),
+                    Pointer::from(Value::Array(call.arguments.clone())),
+                    Lifetime::Default,
+                );
+                for (idx, param) in params.iter().enumerate() {
+                    let (arg_eval, arg_span) = match (call.args.get(idx), &param.default) {
+                        (Some(Some(arg)), _) => arg.clone(),
+                        // defaults can use the parameters before them
+                        (_, Some(default)) => {
+                            (engine.run(default, inner_state.clone())?, default.span())
+                        }
+                        _ => (state.borrow().undefined.clone(), call.span),
+                    };
+                    check_type(
+                        &param.name,
+                        param.annotation.as_ref(),
+                        &arg_eval,
+                        arg_span,
+                        &state.borrow().config,
+                    )?;
+                    inner_state.borrow_mut().insert(
+                        param.name.clone(),
+                        arg_eval,
+                        Lifetime::Default,
+                    );
+                }
+                engine.run_body(body, inner_state)
+            });
+            match tail {
+                Ok(Tail::Done(_)) | Err(Unwind::Return(_)) if discard => {
+                    break Ok(state.borrow().undefined.clone())
+                }
+                Ok(Tail::Done(val)) | Err(Unwind::Return(val)) => break Ok(val),
+                Ok(Tail::Call(mut next)) => {
+                    if state.borrow().config.dynamic_scope {
+                        // The call still sees everything that the caller could, but it's all put in one scope, so
+                        // that a function that calls itself at the end doesn't make scopes inside of scopes forever.
+                        next.scope = rc_mut_new(State::flatten(&next.scope, &base));
+                    }
+                    // the call at the end takes the place of this one
+                    *calls
+                        .borrow_mut()
+                        .last_mut()
+                        .expect("this call is on the stack") = next.frame();
+                    call = next;
+                }
+                Err(Unwind::Error(err)) => break Err(err.or_stack(&calls.borrow()).into()),
+            }
+        };
+        calls.borrow_mut().pop();
+        result
+    })
+}
+
+/// get the name of every variable that's used anywhere inside of `syn`
+fn find_idents_in_syntax(syn: &Syntax) -> Vec<Rc<str>> {
+    let mut idents = Idents::default();
+    idents.visit_syntax(syn);
+    idents.0
+}
@@ -1,16 +1,178 @@
-use std::iter::Peekable;
+use std::collections::VecDeque;
+
+use lazy_regex::regex;
 
 use crate::types::prelude::*;
 
-pub fn tokenize(source: &str) -> SResult<Vec<Token>> {
-    let mut chars = source.chars().peekable();
+pub fn tokenize(source: &str) -> SResult<Vec<(Token, Span)>> {
+    tokenize_cursor(Cursor::new(source.chars()))
+}
+
+/// tokenize without dropping or merging anything, so the spans cover every character of `source`.
+/// Comments are `None`.
+pub fn tokenize_lossless(source: &str) -> SResult<Vec<(Option<Token>, Span)>> {
     let mut token_stream = Vec::new();
+    lex_all(Cursor::new(source.chars()), |tok, span| {
+        token_stream.push((tok, span));
+    })?;
+    Ok(token_stream)
+}
+
+/// tokenize a piece of source code that starts at `start`, like the inside of a string interpolation
+fn tokenize_at(source: &str, start: Span) -> SResult<Vec<(Token, Span)>> {
+    tokenize_cursor(Cursor::starting_at(source.chars(), start, ""))
+}
+
+fn tokenize_cursor<T: Iterator<Item = char>>(chars: Cursor<T>) -> SResult<Vec<(Token, Span)>> {
+    let mut token_stream: Vec<(Token, Span)> = Vec::new();
+    lex_all(chars, |tok, span| push_token(&mut token_stream, tok, span))?;
+    Ok(token_stream)
+}
+
+/// add a token to the end of the stream, skipping comments
+fn push_token(token_stream: &mut Vec<(Token, Span)>, tok: Option<Token>, span: Span) {
+    match (tok, token_stream.last_mut()) {
+        // whitespace on both sides of a comment counts as one run, as wide as the wider side,
+        // so that commenting code never changes how it's grouped
+        (Some(Token::Space(width)), Some((Token::Space(prev_width), prev_span))) => {
+            *prev_width = width.max(*prev_width);
+            *prev_span = prev_span.to(span);
+        }
+        (Some(tok), _) => token_stream.push((tok, span)),
+        (None, _) => {}
+    }
+}
+
+/// A lexer that can be given more source code after it runs out, like when code is typed into the REPL a line at a time
+#[derive(Debug, Default)]
+pub struct ResumableLexer {
+    source: String,
+    tokens: Vec<(Token, Span)>,
+}
+
+impl ResumableLexer {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Add more source code and get all of the tokens so far. Errors that are
+    /// [`incomplete`](DreamBerdError::incomplete) might go away once there's more code.
+    pub fn push(&mut self, text: &str) -> SResult<&[(Token, Span)]> {
+        self.source.push_str(text);
+        // the new text might continue the last token, like by making an identifier longer, so lex it again
+        let resume = self
+            .tokens
+            .last()
+            .map_or_else(|| Span::new(0, 0, 1, 1), |&(_, span)| span);
+        // what comes before the resumed token can change what it is, like whether `//` is a comment
+        let (before, after) = self.source.split_at(resume.start);
+        let new_tokens = tokenize_cursor(Cursor::starting_at(after.chars(), resume, before))?;
+        self.tokens.truncate(self.tokens.len().saturating_sub(1));
+        for (tok, span) in new_tokens {
+            push_token(&mut self.tokens, Some(tok), span);
+        }
+        Ok(&self.tokens)
+    }
+}
+
+/// lex every token in `chars`, passing each one to `emit` along with its span; comments are `None`
+fn lex_all<T: Iterator<Item = char>>(
+    mut chars: Cursor<T>,
+    mut emit: impl FnMut(Option<Token>, Span),
+) -> SResult<()> {
     while chars.peek().is_some() {
-        if let Some(tok) = inner_tokenize(&mut chars)? {
-            token_stream.push(tok);
+        let start = chars.here();
+        let tok = inner_tokenize(&mut chars).map_err(|err| err.or_span(start.to(chars.here())))?;
+        emit(tok, start.to(chars.here()));
+    }
+    Ok(())
+}
+
+/// A peekable stream of characters that keeps track of its position in the source
+struct Cursor<T: Iterator<Item = char>> {
+    chars: T,
+    /// characters that have been peeked but not consumed yet
+    lookahead: VecDeque<char>,
+    /// the character that was consumed most recently
+    last: Option<char>,
+    /// the last character on this line so far that isn't whitespace
+    last_on_line: Option<char>,
+    offset: usize,
+    line: usize,
+    col: usize,
+}
+
+impl<T: Iterator<Item = char>> Cursor<T> {
+    const fn new(chars: T) -> Self {
+        Self {
+            chars,
+            lookahead: VecDeque::new(),
+            last: None,
+            last_on_line: None,
+            offset: 0,
+            line: 1,
+            col: 1,
         }
     }
-    Ok(token_stream)
+
+    /// make a cursor for text that begins at `start` in a larger source, right after `before`
+    fn starting_at(chars: T, start: Span, before: &str) -> Self {
+        Self {
+            chars,
+            lookahead: VecDeque::new(),
+            last: before.chars().next_back(),
+            last_on_line: before
+                .chars()
+                .rev()
+                .take_while(|&char| char != '\n')
+                .find(|char| !char.is_whitespace()),
+            offset: start.start,
+            line: start.line,
+            col: start.col,
+        }
+    }
+
+    fn peek(&mut self) -> Option<&char> {
+        self.peek_nth(0)
+    }
+
+    /// look at the character after the next one
+    fn peek_second(&mut self) -> Option<&char> {
+        self.peek_nth(1)
+    }
+
+    fn peek_nth(&mut self, n: usize) -> Option<&char> {
+        while self.lookahead.len() <= n {
+            self.lookahead.push_back(self.chars.next()?);
+        }
+        self.lookahead.get(n)
+    }
+
+    /// get an empty span at the position of the next character
+    const fn here(&self) -> Span {
+        Span::new(self.offset, self.offset, self.line, self.col)
+    }
+}
+
+impl<T: Iterator<Item = char>> Iterator for Cursor<T> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let next = self.lookahead.pop_front().or_else(|| self.chars.next())?;
+        self.last = Some(next);
+        self.offset += next.len_utf8();
+        if next == '\n' {
+            self.line += 1;
+            self.col = 1;
+            self.last_on_line = None;
+        } else {
+            if !next.is_whitespace() {
+                self.last_on_line = Some(next);
+            }
+            self.col += 1;
+        }
+        Some(next)
+    }
 }
 
 macro_rules! multi_character_pattern {
@@ -25,62 +187,82 @@ macro_rules! multi_character_pattern {
     };
 }
 
-fn lex_string<T: Iterator<Item = char>>(chars: &mut Peekable<T>, end: char) -> SResult<Token> {
+type TokenSegment = StringSegment<Vec<(Token, Span)>>;
+
+fn lex_string<T: Iterator<Item = char>>(chars: &mut Cursor<T>, end: char) -> SResult<Token> {
+    Ok(Token::String(lex_segments(chars, Some(end))?))
+}
+
+/// lex the inside of a string up to the closing `end`, or to the end of the input if there's no `end`
+fn lex_segments<T: Iterator<Item = char>>(
+    chars: &mut Cursor<T>,
+    end: Option<char>,
+) -> SResult<Vec<TokenSegment>> {
     let mut outer_buf = Vec::new();
     let mut string_buf = String::new();
-    while let Some(next) = chars.next() {
-        if next == end {
+    loop {
+        let escape_start = chars.here();
+        let Some(next) = chars.next() else {
+            if let Some(end) = end {
+                return Err(DreamBerdError::lex(format!(
+                    "Unterminated string; expected a closing `{end}`"
+                ))
+                .incomplete());
+            }
+            break;
+        };
+        if Some(next) == end {
             break;
         }
         if matches!(next, '$' | '£' | '¥') && chars.peek() == Some(&'{') {
             chars.next();
-            if !string_buf.is_empty() {
-                outer_buf.push(StringSegment::String(
-                    core::mem::take(&mut string_buf).into(),
-                ));
-            }
-            for next in chars.by_ref() {
-                if next == '}' {
-                    if !string_buf.is_empty() {
-                        outer_buf.push(StringSegment::Ident(
-                            core::mem::take(&mut string_buf).into(),
-                        ));
-                    }
-                    break;
-                }
-                string_buf.push(next);
-            }
+            let (inner, start) = braced(chars, true)?;
+            push_segment(
+                &mut outer_buf,
+                &mut string_buf,
+                StringSegment::Interpolation(tokenize_at(&inner, start)?),
+            );
         } else if next == '{' {
-            let mut ident_buf = String::new();
-            for next in chars.by_ref() {
-                if next == '}' {
-                    break;
-                }
-                ident_buf.push(next);
-            }
+            let (inner, start) = braced(chars, false)?;
             if matches!(chars.peek(), Some('€' | '円' | '₽')) {
                 chars.next();
-                if !string_buf.is_empty() {
-                    outer_buf.push(StringSegment::String(
-                        core::mem::take(&mut string_buf).into(),
-                    ));
-                }
-                outer_buf.push(StringSegment::Ident(ident_buf.into()));
-            } else if ident_buf.contains('$') {
-                let bits = ident_buf.split('$').collect::<Vec<_>>();
-                outer_buf.push(StringSegment::Escudo(bits[0].into(), bits[1].into()));
+                push_segment(
+                    &mut outer_buf,
+                    &mut string_buf,
+                    StringSegment::Interpolation(tokenize_at(&inner, start)?),
+                );
+            } else if let Some((lhs, rhs)) = inner
+                .rsplit_once('$')
+                .filter(|(_, rhs)| !rhs.starts_with('{'))
+            {
+                // escudo: `{lhs$rhs}` is `${lhs.rhs}`
+                let dollar = position_after(start, lhs);
+                let rhs_start = position_after(dollar, "$");
+                let mut tokens = tokenize_at(lhs, start)?;
+                tokens.push((Token::Dot, dollar.to(rhs_start)));
+                tokens.extend(tokenize_at(rhs, rhs_start)?);
+                push_segment(
+                    &mut outer_buf,
+                    &mut string_buf,
+                    StringSegment::Interpolation(tokens),
+                );
             } else {
+                // just some braces; the inside is still part of the string
                 string_buf.push('{');
-                string_buf.push_str(&ident_buf);
+                let mut inner_chars = Cursor::starting_at(inner.chars(), start, "");
+                for segment in lex_segments(&mut inner_chars, None)? {
+                    match segment {
+                        StringSegment::String(str) => string_buf.push_str(&str),
+                        interpolation @ StringSegment::Interpolation(_) => {
+                            push_segment(&mut outer_buf, &mut string_buf, interpolation);
+                        }
+                    }
+                }
                 string_buf.push('}');
             }
         } else if next == '\\' {
-            string_buf.push(next);
-            string_buf.push(
-                chars
-                    .next()
-                    .ok_or_else(|| String::from("Unexpected end of file"))?,
-            );
+            string_buf
+                .push(lex_escape(chars).map_err(|err| err.or_span(escape_start.to(chars.here())))?);
         } else {
             string_buf.push(next);
         }
@@ -88,11 +270,222 @@ fn lex_string<T: Iterator<Item = char>>(chars: &mut Peekable<T>, end: char) -> S
     if !string_buf.is_empty() {
         outer_buf.push(StringSegment::String(string_buf.into()));
     }
-    Ok(Token::String(outer_buf))
+    Ok(outer_buf)
+}
+
+/// push an interpolation after the text that came before it
+fn push_segment(outer_buf: &mut Vec<TokenSegment>, string_buf: &mut String, segment: TokenSegment) {
+    if !string_buf.is_empty() {
+        outer_buf.push(StringSegment::String(core::mem::take(string_buf).into()));
+    }
+    outer_buf.push(segment);
+}
+
+/// get the text up to the `}` that matches an opening `{`, and where that text starts.
+/// In `code`, braces inside of strings don't count.
+fn braced<T: Iterator<Item = char>>(chars: &mut Cursor<T>, code: bool) -> SResult<(String, Span)> {
+    let start = chars.here();
+    let mut buf = String::new();
+    let mut depth = 1;
+    let mut quote = None;
+    loop {
+        let Some(next) = chars.next() else {
+            return Err(
+                DreamBerdError::lex("Expected `}` to close this interpolation")
+                    .with_span(start)
+                    .incomplete(),
+            );
+        };
+        match (quote, next) {
+            (_, '\\') => {
+                buf.push(next);
+                if let Some(escaped) = chars.next() {
+                    buf.push(escaped);
+                }
+                continue;
+            }
+            (Some(end), _) if next == end => quote = None,
+            (None, '{') => depth += 1,
+            (None, '}') => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            (None, _) if code => quote = closing_quote(next),
+            _ => {}
+        }
+        buf.push(next);
+    }
+    Ok((buf, start))
+}
+
+/// get the character that ends a string started by `open`
+const fn closing_quote(open: char) -> Option<char> {
+    match open {
+        '"' => Some('"'),
+        '\'' => Some('\''),
+        '`' => Some('`'),
+        '«' => Some('»'),
+        '»' => Some('«'),
+        '„' => Some('“'),
+        _ => None,
+    }
+}
+
+/// get the position just after `text`, if it starts at `start`
+fn position_after(start: Span, text: &str) -> Span {
+    let mut chars = Cursor::starting_at(text.chars(), start, "");
+    while chars.next().is_some() {}
+    chars.here()
+}
+
+/// get the character represented by an escape sequence, after the `\\`
+fn lex_escape<T: Iterator<Item = char>>(chars: &mut Cursor<T>) -> SResult<char> {
+    let Some(next) = chars.next() else {
+        return Err(DreamBerdError::lex("Unexpected end of file in escape sequence").incomplete());
+    };
+    Ok(match next {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        'u' => {
+            match chars.next() {
+                Some('{') => {}
+                Some(_) => {
+                    return Err(DreamBerdError::lex(
+                        "Expected `{` after `\\u` in unicode escape",
+                    ))
+                }
+                None => {
+                    return Err(
+                        DreamBerdError::lex("Unexpected end of file in escape sequence")
+                            .incomplete(),
+                    )
+                }
+            }
+            let mut hex_buf = String::new();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(digit) if digit.is_ascii_hexdigit() && hex_buf.len() < 6 => {
+                        hex_buf.push(digit);
+                    }
+                    None => {
+                        return Err(DreamBerdError::lex(
+                            "Unexpected end of file in escape sequence",
+                        )
+                        .incomplete())
+                    }
+                    _ => {
+                        return Err(DreamBerdError::lex(
+                            "Unicode escapes must be 1 to 6 hex digits between `{` and `}`",
+                        ))
+                    }
+                }
+            }
+            u32::from_str_radix(&hex_buf, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(|| {
+                    DreamBerdError::lex(format!("`\\u{{{hex_buf}}}` is not a unicode character"))
+                })?
+        }
+        // quotes, currency symbols, and braces can be escaped to use them without ending the string or interpolating
+        '\\' | '"' | '\'' | '`' | '«' | '»' | '„' | '“' | '$' | '£' | '¥' | '€' | '円' | '₽'
+        | '{' | '}' => next,
+        other => {
+            return Err(DreamBerdError::lex(format!(
+                "Unknown escape sequence `\\{other}`"
+            )))
+        }
+    })
+}
+
+/// After `//`, which is either a comment or floor division. `//=` is always an operator, unless it's the start of `//==`.
+/// Otherwise, it's floor division when it's squished between two values, like `7//2`, and a comment everywhere else,
+/// including `x //note` and `x // note`. A comment after an unfinished statement can't end in `!` or `?`, since
+/// that would hide the end of the statement, like in `7 // 2?`; that's an error instead.
+fn double_slash<T: Iterator<Item = char>>(
+    chars: &mut Cursor<T>,
+    before: Option<char>,
+    before_on_line: Option<char>,
+) -> SResult<Option<Token>> {
+    if chars.peek() == Some(&'=') && chars.peek_second() != Some(&'=') {
+        chars.next();
+        return Ok(Some(Token::SlashSlashEq));
+    }
+    let ends_value = |before: char| !matches!(before, '!' | '?' | '(' | '[' | '{' | ',');
+    let after_value = before.is_some_and(|before| !before.is_whitespace() && ends_value(before));
+    let before_value = chars.peek().is_some_and(|after| !after.is_whitespace());
+    if after_value && before_value {
+        return Ok(Some(Token::SlashSlash));
+    }
+    let comment = line_comment(chars);
+    match comment.trim_end().chars().next_back() {
+        Some(end @ ('!' | '?')) if before_on_line.is_some_and(ends_value) => {
+            Err(DreamBerdError::lex(format!(
+                "This comment hides the `{end}` that would end the statement before it"
+            ))
+            .with_note("`//` with spaces around it starts a comment; floor division is squished between two values, like `7//2`"))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// skip the rest of a `//` comment, leaving the newline, and get what it says
+fn line_comment<T: Iterator<Item = char>>(chars: &mut Cursor<T>) -> String {
+    let mut comment = String::new();
+    while chars.peek().is_some_and(|&next| next != '\n') {
+        comment.extend(chars.next());
+    }
+    comment
+}
+
+/// skip the rest of a `/*` comment, including any comments nested inside it
+fn block_comment<T: Iterator<Item = char>>(chars: &mut Cursor<T>) -> SResult<()> {
+    let mut depth = 1;
+    while depth > 0 {
+        match chars.next() {
+            Some('/') if chars.peek() == Some(&'*') => {
+                chars.next();
+                depth += 1;
+            }
+            Some('*') if chars.peek() == Some(&'/') => {
+                chars.next();
+                depth -= 1;
+            }
+            Some(_) => {}
+            None => return Err(DreamBerdError::lex("Unterminated block comment").incomplete()),
+        }
+    }
+    Ok(())
+}
+
+/// After an `=` at the start of a line, check whether the line is a file separator, like `=====` or
+/// `======= add.db =======`, and take the rest of it if it is
+fn file_break<T: Iterator<Item = char>>(chars: &mut Cursor<T>) -> Option<Token> {
+    let mut line = String::from('=');
+    let mut len = 0;
+    while let Some(&next) = chars.peek_nth(len) {
+        if next == '\n' {
+            break;
+        }
+        line.push(next);
+        len += 1;
+    }
+    let captures =
+        regex!(r"^={5,}[ \t]*(?:([^=\s](?:[^=]*[^=\s])?)[ \t]*=*)?[ \t]*\r?$").captures(&line)?;
+    let name = captures.get(1).map(|name| name.as_str().into());
+    for _ in 0..len {
+        chars.next();
+    }
+    Some(Token::FileBreak(name))
 }
 
 fn count_char<T: Iterator<Item = char>, F: Fn(u8) -> Token>(
-    chars: &mut Peekable<T>,
+    chars: &mut Cursor<T>,
     tok: char,
     typ: F,
 ) -> Token {
@@ -104,9 +497,10 @@ fn count_char<T: Iterator<Item = char>, F: Fn(u8) -> Token>(
     typ(count)
 }
 
-fn inner_tokenize<T: Iterator<Item = char>>(chars: &mut Peekable<T>) -> SResult<Option<Token>> {
+fn inner_tokenize<T: Iterator<Item = char>>(chars: &mut Cursor<T>) -> SResult<Option<Token>> {
+    let (before, before_on_line) = (chars.last, chars.last_on_line);
     let Some(char) = chars.next() else {
-        return Err(String::from("Unexpected end of file"));
+        return Err(DreamBerdError::lex("Unexpected end of file").incomplete());
     };
     Ok(Some(match char {
         '{' => Token::LSquirrely,
@@ -117,6 +511,11 @@ fn inner_tokenize<T: Iterator<Item = char>>(chars: &mut Peekable<T>) -> SResult<
         ']' => Token::RSquare,
         ';' => Token::Semicolon,
         ':' => Token::Colon,
+        '.' if chars.peek() == Some(&'.') && chars.peek_second() == Some(&'.') => {
+            chars.next();
+            chars.next();
+            Token::DotDotDot
+        }
         '.' => Token::Dot,
         ',' => Token::Comma,
         '&' => Token::And,
@@ -128,8 +527,20 @@ fn inner_tokenize<T: Iterator<Item = char>>(chars: &mut Peekable<T>) -> SResult<
             multi_character_pattern!(chars Token::Tack; {'=' => Token::TackEq, '>' => Token::Arrow, '-' => Token::TackTack})
         }
         '*' => multi_character_pattern!(chars Token::Star; {'=' => Token::StarEq}),
-        '/' => multi_character_pattern!(chars Token::Slash; {'=' => Token::SlashEq}),
+        '/' => match chars.peek() {
+            Some('/') => {
+                chars.next();
+                return double_slash(chars, before, before_on_line);
+            }
+            Some('*') => {
+                chars.next();
+                block_comment(chars)?;
+                return Ok(None);
+            }
+            _ => multi_character_pattern!(chars Token::Slash; {'=' => Token::SlashEq}),
+        },
         '%' => multi_character_pattern!(chars Token::Percent; {'=' => Token::PercentEq}),
+        '^' => multi_character_pattern!(chars Token::Caret; {'=' => Token::CaretEq}),
         '<' => multi_character_pattern!(chars Token::LCaret; {'=' => Token::LCaretEq}),
         '>' => multi_character_pattern!(chars Token::RCaret; {'=' => Token::RCaretEq}),
         '"' => lex_string(chars, '"')?,
@@ -138,9 +549,13 @@ fn inner_tokenize<T: Iterator<Item = char>>(chars: &mut Peekable<T>) -> SResult<
         '«' => lex_string(chars, '»')?,
         '»' => lex_string(chars, '«')?,
         '„' => lex_string(chars, '“')?,
+        '=' if matches!(before, None | Some('\n')) => {
+            file_break(chars).unwrap_or_else(|| count_char(chars, '=', Token::Equal))
+        }
         '=' => count_char(chars, '=', Token::Equal),
         '!' => count_char(chars, '!', Token::Bang),
         '?' => count_char(chars, '?', Token::Question),
+        '0'..='9' => lex_number(chars, char),
         _ => {
             if char.is_whitespace() {
                 let mut whitespace_count = 1;
@@ -159,17 +574,73 @@ fn inner_tokenize<T: Iterator<Item = char>>(chars: &mut Peekable<T>) -> SResult<
                 Token::Space(whitespace_count)
             } else {
                 let mut ident_buf = String::from(char);
-                while let Some(next) = chars.peek() {
-                    match inner_tokenize(&mut std::iter::once(*next).peekable()) {
-                        Ok(Some(Token::Ident(id))) => {
-                            ident_buf.push_str(&id);
-                            chars.next();
-                        }
-                        _ => break,
+                while let Some(&next) = chars.peek() {
+                    if !is_ident_char(next) {
+                        break;
                     }
+                    ident_buf.push(next);
+                    chars.next();
                 }
                 Token::Ident(ident_buf.into())
             }
         }
     }))
 }
+
+/// check whether `char` can be part of an identifier, instead of being a token on its own
+fn is_ident_char(char: char) -> bool {
+    matches!(
+        inner_tokenize(&mut Cursor::new(std::iter::once(char))),
+        Ok(Some(Token::Ident(_) | Token::Number(_)))
+    )
+}
+
+/// Lex a word that starts with a digit. Numeric literals, like `1_000`, `0.5`, `6.02e23`, or `0xff`, become numbers;
+/// anything else, like `2nd`, is an identifier.
+fn lex_number<T: Iterator<Item = char>>(chars: &mut Cursor<T>, first: char) -> Token {
+    let mut text = String::from(first);
+    let is_decimal =
+        |text: &str| !matches!(text.get(..2), Some("0x" | "0X" | "0b" | "0B" | "0o" | "0O"));
+    while let Some(&next) = chars.peek() {
+        let digit_after = chars.peek_second().is_some_and(char::is_ascii_digit);
+        let continues = match next {
+            '.' => digit_after && is_decimal(&text) && !text.contains(['.', 'e', 'E']),
+            '+' | '-' => digit_after && is_decimal(&text) && text.ends_with(['e', 'E']),
+            _ => is_ident_char(next),
+        };
+        if !continues {
+            break;
+        }
+        text.push(next);
+        chars.next();
+    }
+    // every way of writing a number is spelled the same, so `0x10` and `16` are the same name
+    parse_number(&text).map_or_else(
+        || Token::Ident(text.into()),
+        |num| Token::Number(num.to_string().into()),
+    )
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    // `_` can only separate digits
+    let bytes = text.as_bytes();
+    let separators_ok = text.match_indices('_').all(|(idx, _)| {
+        bytes[idx - 1].is_ascii_alphanumeric()
+            && bytes.get(idx + 1).is_some_and(u8::is_ascii_alphanumeric)
+    });
+    if !separators_ok {
+        return None;
+    }
+    let digits = text.replace('_', "");
+    let radix = match digits.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0b" | "0B") => 2,
+        Some("0o" | "0O") => 8,
+        _ => return digits.parse().ok(),
+    };
+    // integers too big to be exact get rounded, just like decimal literals do
+    #[allow(clippy::cast_precision_loss)]
+    u128::from_str_radix(&digits[2..], radix)
+        .ok()
+        .map(|num| num as f64)
+}
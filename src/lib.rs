@@ -0,0 +1,26 @@
+//! An interpreter for `DreamBerd`.
+//!
+//! Code goes through the [`lexer`] and the [`parser`] to become [`Syntax`](types::Syntax), which [`interpret`]
+//! runs.
+
+#![warn(clippy::pedantic, clippy::nursery)]
+// objects are keyed by `Value`, which can contain mutable pointers
+#![allow(clippy::mutable_key_type)]
+// almost everything can fail with a `DreamBerdError`, and almost nothing is worth marking `#[must_use]`
+#![allow(
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc,
+    clippy::must_use_candidate,
+    clippy::return_self_not_must_use
+)]
+
+mod interpreter;
+pub mod lexer;
+pub mod parser;
+#[cfg(test)]
+mod tests;
+pub mod typecheck;
+pub mod types;
+mod vm;
+
+pub use interpreter::{interpret, run, Flow, Unwind};
@@ -1,24 +1,18 @@
 #![warn(clippy::pedantic, clippy::nursery)]
 
-use std::{
-    error::Error,
-    fs,
-    path::{Path, PathBuf},
-};
+use std::{error::Error, fs, time::Duration};
 
 use dialoguer::Confirm;
 use rustyline::{error::ReadlineError, DefaultEditor};
 
 use clap::{Parser, Subcommand};
-use interpreter::inner_interpret;
-use types::{rc_mut_new, Pointer, RcMut, State, Syntax};
-
-mod interpreter;
-mod lexer;
-mod parser;
-#[cfg(test)]
-mod tests;
-mod types;
+use dreamberd_rs::{
+    interpret, lexer,
+    parser::{self, Incremental, Parsed},
+    typecheck,
+    types::{rc_mut_new, Config, DreamBerdError, Engine, ErrorKind, State, Syntax},
+    Unwind,
+};
 
 #[derive(Parser)]
 struct Args {
@@ -31,102 +25,214 @@ enum SubcommandArg {
     Run {
         /// path to the source file
         path: String,
+        #[command(flatten)]
+        config: ConfigArgs,
     },
     Repl {
         /// path to the source file (optional)
         path: Option<String>,
+        #[command(flatten)]
+        config: ConfigArgs,
+    },
+    /// check the type annotations in a program without running it
+    Typecheck {
+        /// path to the source file
+        path: String,
     },
 }
 
+#[derive(clap::Args)]
+struct ConfigArgs {
+    /// check values against their type annotations when they're bound to a name
+    #[arg(long)]
+    strict_types: bool,
+    /// run functions inside of the scope they're called from, instead of the one they were made in, like
+    /// older versions did
+    #[arg(long)]
+    dynamic_scope: bool,
+    /// how to run the code: walk the syntax tree, or compile it to instructions for a virtual machine first
+    #[arg(long, value_enum, default_value_t)]
+    engine: Engine,
+    /// the most function calls that can be running at once; calls at the end of a function don't count
+    #[arg(long, default_value_t = Config::default().max_depth)]
+    max_depth: usize,
+    /// the most steps the code can take before it's stopped, where every statement in a block and every call
+    /// is a step
+    #[arg(long)]
+    fuel: Option<u64>,
+    /// the most seconds the code can run for before it's stopped
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+}
+
+fn parse_seconds(arg: &str) -> Result<Duration, String> {
+    let secs: f64 = arg.parse().map_err(|err| format!("{err}"))?;
+    Duration::try_from_secs_f64(secs).map_err(|err| format!("{err}"))
+}
+
+impl From<ConfigArgs> for Config {
+    fn from(value: ConfigArgs) -> Self {
+        Self {
+            strict_types: value.strict_types,
+            dynamic_scope: value.dynamic_scope,
+            engine: value.engine,
+            max_depth: value.max_depth,
+            fuel: value.fuel,
+            timeout: value.timeout,
+        }
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
     match args.sub_command {
-        SubcommandArg::Run { path } => {
-            let _result = interpreter::interpret(&file_to_syntax(&PathBuf::from(path))?)?;
-            // println!("{result:?}");
+        SubcommandArg::Run { path, config } => {
+            let source = fs::read_to_string(&path)?;
+            let syntax = source_to_syntax(&source, &path);
+            if let Err(err) = interpret(&syntax, config.into()) {
+                exit_with(&err, &source, &path);
+            }
         }
-        SubcommandArg::Repl { path } => {
-            println!("\x1b[93mRepl - DreamBerd-rs\x1b[0m");
-            //
-            let state = rc_mut_new(State::new());
-            if let Some(path) = path {
-                let syn = file_to_syntax(&PathBuf::from(path))?;
-                let statements = match syn {
-                    Syntax::Block(statements) => statements,
-                    other => vec![other],
-                };
-                for statement in statements {
-                    inner_interpret(&statement, state.clone())?;
-                }
-                // println!("{result}");
-                // println!("{state:?}");
+        SubcommandArg::Typecheck { path } => {
+            let source = fs::read_to_string(&path)?;
+            let syntax = source_to_syntax(&source, &path);
+            let errors = typecheck::typecheck(&syntax);
+            for err in &errors {
+                eprint!("{}", err.render(&source, &path));
             }
+            if !errors.is_empty() {
+                eprintln!("found {} type errors in {path}", errors.len());
+                std::process::exit(1);
+            }
+        }
+        SubcommandArg::Repl { path, config } => repl(path, config.into())?,
+    }
+    Ok(())
+}
 
-            let path = "history";
-
-            let mut rl = DefaultEditor::new()?;
-            if rl.load_history(path).is_err() {
-                println!("No hist");
+fn repl(path: Option<String>, config: Config) -> Result<(), Box<dyn Error>> {
+    println!("\x1b[93mRepl - DreamBerd-rs\x1b[0m");
+    //
+    let state = rc_mut_new(State::with_config(config));
+    if let Some(path) = path {
+        let source = fs::read_to_string(&path)?;
+        let statements = match source_to_syntax(&source, &path) {
+            Syntax::Block(statements, _) => statements,
+            other => vec![other],
+        };
+        for statement in statements {
+            match dreamberd_rs::run(&statement, state.clone()) {
+                Ok(_) => {}
+                // `return` ends the file early
+                Err(Unwind::Return(_)) => break,
+                Err(Unwind::Error(err)) => exit_with(&err, &source, &path),
             }
+        }
+        // println!("{result}");
+        // println!("{state:?}");
+    }
+
+    let path = "history";
 
-            loop {
+    let mut rl = DefaultEditor::new()?;
+    if rl.load_history(path).is_err() {
+        println!("No hist");
+    }
+
+    // code that's been typed in but isn't finished yet
+    let mut pending = Incremental::default();
+    loop {
+        //
+        let prompt = if pending.source().is_empty() {
+            ">>> "
+        } else {
+            "... "
+        };
+        let readline = rl.readline(prompt);
+        match readline {
+            Ok(line) => {
+                rl.add_history_entry(line.as_str())?;
                 //
-                let readline = rl.readline(">>> ");
-                match readline {
-                    Ok(line) => {
-                        rl.add_history_entry(line.as_str())?;
-                        //
-                        if line.is_empty() {
-                            return Ok(());
-                        }
-                        let result = run_input(&line, state.clone());
-                        match result {
-                            Ok(ptr) => {
-                                if ptr != state.borrow().undefined {
-                                    println!("{ptr:?}");
-                                }
-                            }
-                            Err(err) => println!("Error: {err}"),
-                        }
+                let result = if line.is_empty() {
+                    if pending.source().is_empty() {
+                        return Ok(());
                     }
-                    Err(ReadlineError::Interrupted) => {
-                        // Bye bye! - awesome
-                        println!("\nCTRL-C");
-                        let confirmation = Confirm::new()
-                            .with_prompt("Do you want to leave the repl?")
-                            .interact()
-                            .unwrap();
-                        if confirmation {
-                            println!("Leaving");
-                            break;
+                    // an empty line means that the code is as finished as it's going to get
+                    pending.finish().map(Parsed::Complete)
+                } else {
+                    pending.push(&format!("{line}\n"))
+                };
+                // each input gets all of the fuel and time again
+                state.borrow().budget.refill();
+                let result = match result {
+                    Ok(Parsed::Incomplete) => continue,
+                    Ok(Parsed::Complete(syntax)) => dreamberd_rs::run(&syntax, state.clone()),
+                    Err(err) => Err(err.into()),
+                };
+                match result {
+                    Ok(ptr) => {
+                        if ptr != state.borrow().undefined {
+                            println!("{ptr:?}");
                         }
                     }
-                    Err(ReadlineError::Eof) => {
-                        println!("CTRL-D");
-                        break;
-                    }
-                    Err(err) => {
-                        println!("Error: {err:?}");
-                        break;
+                    // `return` ends the program, and here the repl is the program
+                    Err(Unwind::Return(_)) => break,
+                    Err(Unwind::Error(err)) => {
+                        print!("{}", err.render(pending.source(), "<repl>"));
                     }
                 }
-                rl.save_history(path).unwrap();
+                pending = Incremental::default();
+            }
+            Err(ReadlineError::Interrupted) => {
+                // Bye bye! - awesome
+                println!("\nCTRL-C");
+                let confirmation = Confirm::new()
+                    .with_prompt("Do you want to leave the repl?")
+                    .interact()
+                    .unwrap();
+                if confirmation {
+                    println!("Leaving");
+                    break;
+                }
+            }
+            Err(ReadlineError::Eof) => {
+                println!("CTRL-D");
+                break;
+            }
+            Err(err) => {
+                println!("Error: {err:?}");
+                break;
             }
         }
+        rl.save_history(path).unwrap();
     }
     Ok(())
 }
 
-fn run_input(input: &str, context: RcMut<State>) -> Result<Pointer, Box<dyn Error>> {
-    Ok(inner_interpret(
-        &parser::parse(lexer::tokenize(&format!("{{{input}}}"))?)?,
-        context,
-    )?)
+/// lex and parse a file, or print every syntax error in it and quit
+fn source_to_syntax(source: &str, path: &str) -> Syntax {
+    let tokens = lexer::tokenize(source).unwrap_or_else(|err| exit_with(&err, source, path));
+    // println!("{tokens:?}");
+    let (syntax, errors) = parser::parse_recovering(tokens);
+    if let Some((last, rest)) = errors.split_last() {
+        for err in rest {
+            eprint!("{}", err.render(source, path));
+        }
+        exit_with(last, source, path);
+    }
+    syntax
 }
 
-fn file_to_syntax(path: &Path) -> Result<Syntax, Box<dyn Error>> {
-    let file = format!("{{{}}}", fs::read_to_string(path)?);
-    let tokens = lexer::tokenize(&file)?;
-    // println!("{tokens:?}");
-    parser::parse(tokens).map_err(Into::into)
+/// print the error and quit; programs that don't lex or parse exit with status 2, and programs that fail while running exit with status 1
+fn exit_with(err: &DreamBerdError, source: &str, path: &str) -> ! {
+    eprint!("{}", err.render(source, path));
+    std::process::exit(match err.kind {
+        ErrorKind::Lex | ErrorKind::Parse => 2,
+        ErrorKind::Runtime
+        | ErrorKind::Assignment
+        | ErrorKind::Arity
+        | ErrorKind::Type
+        | ErrorKind::OutOfFuel
+        | ErrorKind::Timeout => 1,
+    })
 }
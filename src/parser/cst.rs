@@ -0,0 +1,101 @@
+use std::{iter::Peekable, vec::IntoIter};
+
+use crate::types::prelude::*;
+
+type LosslessTokens = Peekable<IntoIter<(Option<Token>, Span)>>;
+
+/// Build a concrete syntax tree from the output of [`tokenize_lossless`](crate::lexer::tokenize_lossless)
+pub fn parse_cst(source: &str, tokens: Vec<(Option<Token>, Span)>) -> SResult<Cst> {
+    let mut tokens = tokens.into_iter().peekable();
+    let children = group_children(source, &mut tokens, None)?;
+    Ok(Cst::File(children, Span::new(0, source.len(), 1, 1)))
+}
+
+const fn closer(open: &Token) -> Option<Token> {
+    match open {
+        Token::LSquirrely => Some(Token::RSquirrely),
+        Token::LParen => Some(Token::RParen),
+        Token::LSquare => Some(Token::RSquare),
+        _ => None,
+    }
+}
+
+/// get the children of a group, up to and including the token that closes `open`.
+/// Without `open`, this gets the children of the whole file.
+fn group_children(
+    source: &str,
+    tokens: &mut LosslessTokens,
+    open: Option<(&Token, Span)>,
+) -> SResult<Vec<Cst>> {
+    let close = open.and_then(|(open, _)| closer(open));
+    // only blocks and files are made of statements
+    let has_statements = matches!(open, None | Some((Token::LSquirrely, _)));
+    let mut children = Vec::new();
+    let mut statement: Vec<Cst> = Vec::new();
+    loop {
+        let Some((tok, span)) = tokens.next() else {
+            if let Some((_, start)) = open {
+                return Err(DreamBerdError::parse(format!(
+                    "Expected `{}` to close this group",
+                    close.as_ref().map_or("", bracket)
+                ))
+                .with_span(start));
+            }
+            break;
+        };
+        let text = source[span.start..span.end].into();
+        let item = match tok {
+            Some(tok) if Some(&tok) == close.as_ref() => {
+                flush_statement(&mut children, &mut statement);
+                children.push(Cst::Token(tok, text, span));
+                break;
+            }
+            Some(tok @ (Token::RSquirrely | Token::RParen | Token::RSquare)) => {
+                return Err(
+                    DreamBerdError::parse(format!("Unexpected `{}`", bracket(&tok)))
+                        .with_span(span),
+                )
+            }
+            Some(tok) if closer(&tok).is_some() => {
+                let mut inner = vec![Cst::Token(tok.clone(), text, span)];
+                inner.extend(group_children(source, tokens, Some((&tok, span)))?);
+                let span = span.to(inner.last().map_or(span, Cst::span));
+                Cst::Group(inner, span)
+            }
+            Some(tok) => Cst::Token(tok, text, span),
+            None => Cst::Comment(text, span),
+        };
+        if !has_statements || (statement.is_empty() && item.is_trivia()) {
+            children.push(item);
+        } else {
+            let ends_statement =
+                matches!(item, Cst::Token(Token::Bang(_) | Token::Question(_), ..));
+            statement.push(item);
+            if ends_statement {
+                flush_statement(&mut children, &mut statement);
+            }
+        }
+    }
+    flush_statement(&mut children, &mut statement);
+    Ok(children)
+}
+
+/// finish the statement being built, if there is one
+fn flush_statement(children: &mut Vec<Cst>, statement: &mut Vec<Cst>) {
+    if let (Some(first), Some(last)) = (statement.first(), statement.last()) {
+        let span = first.span().to(last.span());
+        children.push(Cst::Statement(core::mem::take(statement), span));
+    }
+}
+
+const fn bracket(tok: &Token) -> &'static str {
+    match tok {
+        Token::LSquirrely => "{",
+        Token::RSquirrely => "}",
+        Token::LParen => "(",
+        Token::RParen => ")",
+        Token::LSquare => "[",
+        Token::RSquare => "]",
+        _ => "",
+    }
+}
@@ -1,23 +1,35 @@
-use std::iter::Peekable;
+use std::{iter::Peekable, rc::Rc};
 
 use crate::types::prelude::*;
 
-use super::{consume_whitespace, inner_parse};
+use super::{consume_whitespace, inner_parse, Tokens};
 
 #[derive(Debug, Clone)]
 enum GroupThingieEnum {
     Syntax(Syntax, u8),
-    Operation(Operation, u8),
-    Unary(UnaryOperation, u8),
+    Operation(Operation, u8, Span),
+    Unary(UnaryOperation, u8, Span),
 }
 
-pub(super) fn parse_group<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> SResult<Syntax> {
+pub(super) fn parse_group(tokens: &mut Tokens) -> SResult<Syntax> {
+    let start = tokens.peek_span();
     let new_toks = fancify_toks(tokens)?;
+    // an operator at the end of the code needs something after it
+    if tokens.peek().is_none()
+        && matches!(
+            new_toks.last(),
+            None | Some(GroupThingieEnum::Operation(..))
+        )
+    {
+        return Err(DreamBerdError::parse("Expected an expression")
+            .with_span(tokens.peek_span())
+            .incomplete());
+    }
     let max_spc = new_toks
         .iter()
         .map(|group| match group {
-            GroupThingieEnum::Unary(_, spc)
-            | GroupThingieEnum::Operation(_, spc)
+            GroupThingieEnum::Unary(_, spc, _)
+            | GroupThingieEnum::Operation(_, spc, _)
             | GroupThingieEnum::Syntax(_, spc) => spc,
         })
         .max()
@@ -25,21 +37,25 @@ pub(super) fn parse_group<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -
         .unwrap_or(0);
 
     // println!("{new_toks:?}");
-    inner_parse_group_better(&mut new_toks.into_iter().rev().peekable(), max_spc + 1)
+    inner_parse_group_better(
+        &mut new_toks.into_iter().rev().peekable(),
+        max_spc.saturating_add(1),
+        start,
+    )
 }
 
-fn fancify_toks<T: Iterator<Item = Token>>(
-    tokens: &mut Peekable<T>,
-) -> SResult<Vec<GroupThingieEnum>> {
+fn fancify_toks(tokens: &mut Tokens) -> SResult<Vec<GroupThingieEnum>> {
     let mut toks = Vec::new();
     loop {
         let mut whitespace = consume_whitespace(tokens);
+        let span = tokens.peek_span();
         match tokens.peek() {
             Some(Token::TackTack) => {
                 tokens.next();
                 toks.push(GroupThingieEnum::Unary(
                     UnaryOperation::Decrement,
                     whitespace,
+                    span,
                 ));
             }
             Some(Token::PlusPlus) => {
@@ -47,6 +63,7 @@ fn fancify_toks<T: Iterator<Item = Token>>(
                 toks.push(GroupThingieEnum::Unary(
                     UnaryOperation::Increment,
                     whitespace,
+                    span,
                 ));
             }
             Some(
@@ -55,15 +72,41 @@ fn fancify_toks<T: Iterator<Item = Token>>(
                 | Token::Question(_)
                 | Token::RSquare
                 | Token::Comma
-                | Token::RSquirrely,
+                | Token::RSquirrely
+                | Token::FileBreak(_),
             )
             | None => break,
             Some(tok) => {
                 if let Ok(op) = Operation::try_from(tok.clone()) {
                     tokens.next();
-                    whitespace += consume_whitespace(tokens);
-                    toks.push(GroupThingieEnum::Operation(op, whitespace));
+                    whitespace = whitespace.saturating_add(consume_whitespace(tokens));
+                    toks.push(GroupThingieEnum::Operation(op, whitespace, span));
                 } else {
+                    // `[` right after a value, with no space between them, indexes it instead of making an array
+                    if tok == &Token::LSquare
+                        && whitespace == 0
+                        && matches!(toks.last(), Some(GroupThingieEnum::Syntax(..)))
+                    {
+                        tokens.next();
+                        let (target, space) = index_target(&mut toks, span)?;
+                        let index = parse_group(tokens)?;
+                        consume_whitespace(tokens);
+                        if tokens.next() != Some(Token::RSquare) {
+                            return Err(DreamBerdError::parse("Expected `]` to close this index")
+                                .with_span(span));
+                        }
+                        let span = target.span().to(tokens.last_span());
+                        toks.push(GroupThingieEnum::Syntax(
+                            Syntax::Operation(
+                                Box::new(target),
+                                Operation::Index,
+                                Box::new(index),
+                                span,
+                            ),
+                            space,
+                        ));
+                        continue;
+                    }
                     let inner = inner_parse(tokens)?;
                     if matches!(inner, Syntax::Statement(..)) {
                         toks.push(GroupThingieEnum::Syntax(inner, whitespace));
@@ -77,51 +120,88 @@ fn fancify_toks<T: Iterator<Item = Token>>(
     Ok(toks)
 }
 
+/// Take the thing being indexed off the end of `toks`, along with the whitespace before it. That's everything
+/// right before the `[` that isn't separated by spaces, so `a.b[0]` indexes `a.b` and `f(x)[0]` indexes `f(x)`.
+fn index_target(toks: &mut Vec<GroupThingieEnum>, at: Span) -> SResult<(Syntax, u8)> {
+    let mut start = toks.len();
+    while let Some(GroupThingieEnum::Syntax(_, space)) =
+        start.checked_sub(1).and_then(|idx| toks.get(idx))
+    {
+        start -= 1;
+        if *space > 0 {
+            break;
+        }
+        if let Some(GroupThingieEnum::Operation(Operation::Dot, 0, _)) =
+            start.checked_sub(1).and_then(|idx| toks.get(idx))
+        {
+            start -= 1;
+        }
+    }
+    let run = toks.split_off(start);
+    let space = match run.first() {
+        Some(GroupThingieEnum::Syntax(_, space)) => *space,
+        _ => 0,
+    };
+    let target = inner_parse_group_better(&mut run.into_iter().rev().peekable(), 1, at)?;
+    Ok((target, space))
+}
+
+/// `at` is where to report an error if the group runs out of tokens
 fn inner_parse_group_better<T: Iterator<Item = GroupThingieEnum>>(
     tokens: &mut Peekable<T>,
     spacing: u8,
+    at: Span,
 ) -> SResult<Syntax> {
     if spacing == 0 {
         return match tokens.next() {
             Some(GroupThingieEnum::Syntax(lhs, _)) => Ok(lhs),
-            Some(GroupThingieEnum::Unary(unary, spc)) => Ok(Syntax::UnaryOperation(
-                unary,
-                Box::new(inner_parse_group_better(tokens, spc + 1)?),
-            )),
-            Some(other) => Err(format!("Expected expression; got `{other:?}`")),
-            None => Err(String::from("Unexpected EOF")),
+            Some(GroupThingieEnum::Unary(unary, spc, span)) => {
+                let operand = inner_parse_group_better(tokens, spc.saturating_add(1), span)?;
+                let span = span.to(operand.span());
+                Ok(Syntax::UnaryOperation(unary, Box::new(operand), span))
+            }
+            Some(GroupThingieEnum::Operation(op, _, span)) => Err(DreamBerdError::parse(format!(
+                "Expected expression; got `{op}`"
+            ))
+            .with_span(span)),
+            None => Err(DreamBerdError::parse("Expected an expression").with_span(at)),
         };
     }
-    if let Some(GroupThingieEnum::Unary(unary, spc)) = tokens.peek() {
+    if let Some(GroupThingieEnum::Unary(unary, spc, span)) = tokens.peek() {
         let unary = unary.clone();
         let spc = *spc;
+        let span = *span;
         tokens.next();
-        return Ok(Syntax::UnaryOperation(
-            unary,
-            Box::new(inner_parse_group_better(tokens, spc + 1)?),
-        ));
+        let operand = inner_parse_group_better(tokens, spc.saturating_add(1), span)?;
+        let span = span.to(operand.span());
+        return Ok(Syntax::UnaryOperation(unary, Box::new(operand), span));
     }
-    let rhs = inner_parse_group_better(tokens, spacing - 1)?;
+    let rhs = inner_parse_group_better(tokens, spacing - 1, at)?;
     // println!("{rhs}");
     let starter_val = match tokens.peek() {
-        Some(GroupThingieEnum::Operation(op, spc)) if *spc < spacing => {
+        Some(GroupThingieEnum::Operation(op, spc, span)) if *spc < spacing => {
             let op = *op;
+            let span = *span;
             tokens.next();
-            let lhs = inner_parse_group_better(tokens, spacing)?;
+            let lhs = inner_parse_group_better(tokens, spacing, span)?;
             make_operation(lhs, op, rhs)
         }
         _ => Ok(rhs),
     }?;
     match tokens.peek() {
         Some(GroupThingieEnum::Syntax(_, spc)) if *spc <= spacing => {
-            let Some(GroupThingieEnum::Syntax(func, _)) = tokens.next() else {unreachable!()};
+            let Some(GroupThingieEnum::Syntax(func, _)) = tokens.next() else {
+                unreachable!()
+            };
+            let span = func.span().to(starter_val.span());
             let args = match starter_val {
-                Syntax::Block(args) => args,
+                Syntax::Block(args, _) => args,
                 other => vec![other],
             };
             Ok(Syntax::UnaryOperation(
-                UnaryOperation::Call(args),
+                UnaryOperation::Call(args.into()),
                 Box::new(func),
+                span,
             ))
         }
         _ => Ok(starter_val),
@@ -130,23 +210,43 @@ fn inner_parse_group_better<T: Iterator<Item = GroupThingieEnum>>(
 
 /// if `op` is `->`, try to make it into a function
 fn make_operation(left: Syntax, op: Operation, right: Syntax) -> SResult<Syntax> {
-    if op == Operation::Arrow {
-        // println!("{left:?} -> {right:?}");
-        let input = match left {
-            Syntax::Block(vals) => vals
-                .into_iter()
-                .map(|syn| match syn {
-                    Syntax::Ident(ident) => Ok(ident),
-                    other => Err(format!(
-                        "Function input can only have identifiers, not {other:?}"
-                    )),
-                })
-                .collect::<Result<Vec<_>, _>>()?,
-            Syntax::Ident(ident) => vec![ident],
-            other => return Err(format!("Function input can only have identifier or parenthesized list of values; got {other}")),
-        };
-        Ok(Syntax::Function(input, Box::new(right)))
-    } else {
-        Ok(Syntax::Operation(Box::new(left), op, Box::new(right)))
+    let span = left.span().to(right.span());
+    match (op, right) {
+        (Operation::Arrow, right) => {
+            // println!("{left:?} -> {right:?}");
+            let params_span = left.span();
+            let input = match left {
+                Syntax::Block(vals, _) => vals.iter().map(param).collect::<SResult<Vec<_>>>()?,
+                Syntax::Ident(..)
+                | Syntax::Annotated(..)
+                | Syntax::Operation(_, Operation::Equal(1), ..)
+                | Syntax::UnaryOperation(UnaryOperation::Rest, ..) => vec![param(&left)?],
+                other => return Err(DreamBerdError::parse(format!("Function input can only have identifier or parenthesized list of values; got {other}")).with_span(other.span())),
+            };
+            if let Some(rest) = Param::misplaced_rest(&input) {
+                return Err(DreamBerdError::parse(format!(
+                    "`...{}` has to be the last parameter",
+                    rest.name
+                ))
+                .with_span(params_span));
+            }
+            Ok(Syntax::Function(input, Rc::new(right), span))
+        }
+        // `a.b: T` annotates `a.b`, not just `b`
+        (Operation::Dot, Syntax::Annotated(inner, annotation, _)) => Ok(Syntax::Annotated(
+            Box::new(Syntax::Operation(Box::new(left), op, inner, span)),
+            annotation,
+            span,
+        )),
+        (op, right) => Ok(Syntax::Operation(Box::new(left), op, Box::new(right), span)),
     }
 }
+
+fn param(syn: &Syntax) -> SResult<Param> {
+    Param::from_syntax(syn).ok_or_else(|| {
+        DreamBerdError::parse(format!(
+            "Function input can only have identifiers, not {syn:?}"
+        ))
+        .with_span(syn.span())
+    })
+}
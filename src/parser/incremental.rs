@@ -0,0 +1,54 @@
+use crate::{lexer::ResumableLexer, types::prelude::*};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Parsed {
+    Complete(Syntax),
+    /// nothing is wrong yet, but a block, group, string, or something else still needs to be finished
+    Incomplete,
+}
+
+/// Parses code that's given to it a piece at a time, like lines typed into the REPL
+#[derive(Debug, Default)]
+pub struct Incremental {
+    lexer: ResumableLexer,
+    /// the top-level statements at the start that are finished, so that they aren't parsed again
+    pub finished: Vec<Syntax>,
+    /// how many tokens the finished statements take up
+    finished_tokens: usize,
+}
+
+impl Incremental {
+    pub fn source(&self) -> &str {
+        self.lexer.source()
+    }
+
+    /// add more code, and parse everything so far
+    pub fn push(&mut self, text: &str) -> SResult<Parsed> {
+        match self.parse(text) {
+            Ok(syntax) => Ok(Parsed::Complete(syntax)),
+            Err(err) if err.incomplete => Ok(Parsed::Incomplete),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// parse everything so far, assuming that no more code is coming
+    pub fn finish(&mut self) -> SResult<Syntax> {
+        self.parse("")
+    }
+
+    /// Parse everything so far. Only the code after the finished statements is parsed, and any statements there
+    /// that are now finished are kept for next time.
+    fn parse(&mut self, text: &str) -> SResult<Syntax> {
+        let tokens = self.lexer.push(text)?;
+        let (finished, taken) = super::finished_statements(tokens[self.finished_tokens..].to_vec());
+        self.finished.extend(finished);
+        self.finished_tokens += taken;
+        let rest = tokens[self.finished_tokens..].to_vec();
+        match tokens.first() {
+            Some(&(_, start)) if !self.finished.is_empty() => {
+                super::parse_after(self.finished.clone(), start, rest)
+            }
+            _ => super::parse(rest),
+        }
+    }
+}
@@ -1,268 +1,676 @@
-use std::iter::Peekable;
+use std::{iter::Peekable, rc::Rc};
 
-use crate::types::prelude::*;
+use crate::types::{
+    prelude::*,
+    visit::{fold_children, Fold},
+};
 
+pub mod cst;
 mod grouping;
+mod incremental;
 
-pub fn parse(tokens: Vec<Token>) -> SResult<Syntax> {
-    let mut tokens = tokens.into_iter().peekable();
-    let mut syntax = Vec::new();
-    while tokens.peek().is_some() {
-        syntax.push(grouping::parse_group(&mut tokens)?);
+pub use incremental::{Incremental, Parsed};
+
+pub fn parse(tokens: Vec<(Token, Span)>) -> SResult<Syntax> {
+    let mut tokens = Tokens::new(tokens);
+    file(&mut tokens).map_err(|err| {
+        // if we needed another token, there might be more code that fixes it
+        if tokens.ran_out {
+            err.incomplete()
+        } else {
+            err
+        }
+    })
+}
+
+/// Parse as much as possible, instead of stopping at the first error.
+///
+/// Statements that have errors in them become [`Syntax::Error`], and parsing picks up again after the next `!` or `?`, or at the end of the block.
+pub fn parse_recovering(tokens: Vec<(Token, Span)>) -> (Syntax, Vec<DreamBerdError>) {
+    let mut tokens = Tokens::new(tokens);
+    tokens.errors = Some(Vec::new());
+    // all of the errors are recovered from
+    let syntax =
+        file(&mut tokens).unwrap_or_else(|err| Syntax::Error(err.span.unwrap_or_default()));
+    (syntax, tokens.errors.unwrap_or_default())
+}
+
+/// Parse the finished statements at the start of `tokens`: ones that end with `!` or `?`, with more code after
+/// them, so that more code can't change them. Gives back the statements and how many tokens they take up.
+fn finished_statements(tokens: Vec<(Token, Span)>) -> (Vec<Syntax>, usize) {
+    let total = tokens.len();
+    let mut tokens = Tokens::new(tokens);
+    let mut finished = Vec::new();
+    let mut taken = 0;
+    loop {
+        consume_whitespace(&mut tokens);
+        if matches!(
+            tokens.peek(),
+            None | Some(Token::RSquirrely | Token::FileBreak(_))
+        ) {
+            break;
+        }
+        let Ok(inner) = grouping::parse_group(&mut tokens) else {
+            break;
+        };
+        let statement = consume_bang(inner, &mut tokens);
+        // the last token might still turn into something else, like `!` into `!!`
+        if !matches!(statement, Syntax::Statement(..)) || tokens.taken >= total {
+            break;
+        }
+        finished.push(statement);
+        taken = tokens.taken;
+    }
+    (finished, taken)
+}
+
+/// Parse the rest of a file, after statements that [`finished_statements`] already parsed. `start` is where
+/// the file starts.
+fn parse_after(finished: Vec<Syntax>, start: Span, tokens: Vec<(Token, Span)>) -> SResult<Syntax> {
+    let mut tokens = Tokens::new(tokens);
+    file_after(&mut tokens, finished, start).map_err(|err| {
+        if tokens.ran_out {
+            err.incomplete()
+        } else {
+            err
+        }
+    })
+}
+
+fn file(tokens: &mut Tokens) -> SResult<Syntax> {
+    let start = tokens.peek_span();
+    file_after(tokens, Vec::new(), start)
+}
+
+/// parse a file whose first statements have already been parsed
+fn file_after(tokens: &mut Tokens, finished: Vec<Syntax>, start: Span) -> SResult<Syntax> {
+    let mut syntax = finished;
+    syntax.extend(file_statements(tokens)?);
+    if !matches!(tokens.peek(), Some(Token::FileBreak(_))) {
+        return Ok(optimize(Syntax::Block(
+            syntax,
+            start.to(tokens.last_span()),
+        )));
+    }
+    // the source is split up into files by `=====` lines, and anything before the first one is a file without a name
+    let mut files = Vec::new();
+    if !syntax.is_empty() {
+        let span = start.to(tokens.last_span());
+        files.push(Syntax::File(
+            None,
+            Box::new(Syntax::Block(syntax, span)),
+            span,
+        ));
+    }
+    while let Some(Token::FileBreak(name)) = tokens.next() {
+        let file_start = tokens.last_span();
+        let body_start = tokens.peek_span();
+        let body = file_statements(tokens)?;
+        let body = Syntax::Block(body, body_start.to(tokens.last_span()));
+        files.push(Syntax::File(
+            name,
+            Box::new(body),
+            file_start.to(tokens.last_span()),
+        ));
     }
-    Ok(optimize(Syntax::Block(syntax)))
+    Ok(optimize(Syntax::Block(files, start.to(tokens.last_span()))))
 }
 
-fn inner_parse<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> SResult<Syntax> {
+/// parse statements up to the end of the file, or up to the `=====` that starts the next one
+fn file_statements(tokens: &mut Tokens) -> SResult<Vec<Syntax>> {
+    let mut syntax = statements(tokens)?;
+    // the only other thing that stops a file's statements early is a `}` without a `{`
+    while let Some(tok) = tokens.peek() {
+        if matches!(tok, Token::FileBreak(_)) {
+            break;
+        }
+        let err = DreamBerdError::parse(format!("Unexpected token `{tok:?}`"));
+        let span = tokens.peek_span();
+        tokens.recover(err.with_span(span))?;
+        tokens.next();
+        syntax.extend(statements(tokens)?);
+    }
+    Ok(syntax)
+}
+
+/// A stream of tokens that remembers where each of them came from
+struct Tokens {
+    stream: Peekable<std::vec::IntoIter<(Token, Span)>>,
+    last: Span,
+    /// how many tokens have been consumed
+    taken: usize,
+    /// whether we've tried to take a token after the last one
+    ran_out: bool,
+    /// the errors that have been recovered from, if we're recovering from errors at all
+    errors: Option<Vec<DreamBerdError>>,
+}
+
+impl Tokens {
+    fn new(tokens: Vec<(Token, Span)>) -> Self {
+        Self {
+            stream: tokens.into_iter().peekable(),
+            last: Span::default(),
+            taken: 0,
+            ran_out: false,
+            errors: None,
+        }
+    }
+
+    /// keep track of an error and carry on, if we're recovering from errors; otherwise, give it back
+    fn recover(&mut self, err: DreamBerdError) -> SResult<()> {
+        match &mut self.errors {
+            Some(errors) => {
+                errors.push(err);
+                Ok(())
+            }
+            None => Err(err),
+        }
+    }
+
+    /// Skip the rest of a statement that has an error in it, up to and including the next `!` or `?`,
+    /// or up to the `}` that ends the block or the `=====` that ends the file. Brackets inside of the
+    /// statement are skipped as a whole.
+    fn synchronize(&mut self) {
+        let mut depth = 0usize;
+        while let Some(tok) = self.peek() {
+            match tok {
+                Token::Bang(_) | Token::Question(_) if depth == 0 => {
+                    self.next();
+                    return;
+                }
+                Token::RSquirrely if depth == 0 => return,
+                Token::FileBreak(_) => return,
+                Token::LSquirrely | Token::LParen | Token::LSquare => depth += 1,
+                Token::RSquirrely | Token::RParen | Token::RSquare => {
+                    depth = depth.saturating_sub(1);
+                }
+                _ => {}
+            }
+            self.next();
+        }
+    }
+
+    fn peek(&mut self) -> Option<&Token> {
+        self.stream.peek().map(|(tok, _)| tok)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let Some((tok, span)) = self.stream.next() else {
+            self.ran_out = true;
+            return None;
+        };
+        self.last = span;
+        self.taken += 1;
+        Some(tok)
+    }
+
+    /// get the span of the next token, or an empty span at the end of the last one
+    fn peek_span(&mut self) -> Span {
+        match self.stream.peek() {
+            Some((_, span)) => *span,
+            None => Span::new(self.last.end, self.last.end, self.last.line, self.last.col),
+        }
+    }
+
+    /// get the span of the most recently consumed token
+    const fn last_span(&self) -> Span {
+        self.last
+    }
+}
+
+/// parse statements until the end of the block or the end of the file
+fn statements(tokens: &mut Tokens) -> SResult<Vec<Syntax>> {
+    let mut statements_buf = Vec::new();
+    while let Some(tok) = tokens.peek() {
+        match tok {
+            Token::RSquirrely | Token::FileBreak(_) => break,
+            Token::Space(_) => {
+                tokens.next();
+                continue;
+            }
+            _ => {}
+        }
+        let start = tokens.peek_span();
+        match grouping::parse_group(tokens) {
+            Ok(inner) => statements_buf.push(consume_bang(inner, tokens)),
+            Err(err) => {
+                tokens.recover(err)?;
+                tokens.synchronize();
+                statements_buf.push(Syntax::Error(start.to(tokens.last_span())));
+            }
+        }
+    }
+    Ok(statements_buf)
+}
+
+fn inner_parse(tokens: &mut Tokens) -> SResult<Syntax> {
     // println!("{:?}", tokens.peek());
+    let start = tokens.peek_span();
     match tokens.next() {
-        Some(Token::String(str)) => Ok(Syntax::String(str)),
-        Some(Token::Semicolon) => Ok(Syntax::UnaryOperation(
-            UnaryOperation::Negate,
-            Box::new(inner_parse(tokens)?),
+        Some(Token::String(segments)) => Ok(Syntax::String(
+            segments
+                .into_iter()
+                .map(|segment| match segment {
+                    StringSegment::String(str) => Ok(StringSegment::String(str)),
+                    StringSegment::Interpolation(inner) => {
+                        interpolation(inner, start).map(StringSegment::Interpolation)
+                    }
+                })
+                .collect::<SResult<_>>()?,
+            start,
         )),
+        Some(Token::DotDotDot) => {
+            let inner = inner_parse(tokens)?;
+            let span = start.to(inner.span());
+            Ok(Syntax::UnaryOperation(
+                UnaryOperation::Rest,
+                Box::new(inner),
+                span,
+            ))
+        }
+        Some(Token::Semicolon) => {
+            let inner = inner_parse(tokens)?;
+            let span = start.to(inner.span());
+            Ok(Syntax::UnaryOperation(
+                UnaryOperation::Negate,
+                Box::new(inner),
+                span,
+            ))
+        }
         Some(Token::Ident(id)) => {
             if id.as_ref() == "const" || id.as_ref() == "var" {
                 consume_whitespace(tokens);
-                declare(tokens, &id)
+                declare(tokens, &id, start)
+            } else if id.as_ref() == "export" && matches!(tokens.peek(), Some(Token::Space(_))) {
+                export(tokens, start)
             } else {
                 match tokens.peek() {
                     Some(Token::Colon) => {
                         tokens.next();
-                        consume_whitespace(tokens);
-                        get_type(tokens)?;
-                        Ok(Syntax::Ident(id))
+                        let annotation = get_type(tokens)?;
+                        Ok(Syntax::Annotated(
+                            Box::new(Syntax::Ident(id, start)),
+                            annotation,
+                            start.to(tokens.last_span()),
+                        ))
                     }
                     // get the value of the variable
-                    _ => Ok(Syntax::Ident(id)),
+                    _ => Ok(Syntax::Ident(id, start)),
                 }
             }
         }
+        // a number evaluates to itself, unless it's been redefined
+        Some(Token::Number(num)) => Ok(Syntax::Ident(num, start)),
         Some(Token::LSquirrely) => {
-            let mut statements_buf = Vec::new();
-            while let Some(tok) = tokens.peek() {
-                match tok {
-                    Token::RSquirrely => break,
-                    Token::Space(_) => {
-                        tokens.next();
-                        continue;
-                    }
-                    _ => {}
-                }
-                let inner = grouping::parse_group::<T>(tokens)?;
-                statements_buf.push(consume_bang(inner, tokens));
-            }
+            let statements_buf = statements(tokens)?;
             if tokens.next() == Some(Token::RSquirrely) {
-                Ok(Syntax::Block(statements_buf))
+                Ok(Syntax::Block(statements_buf, start.to(tokens.last_span())))
             } else {
-                Err(String::from("Expected `}`"))
+                Err(DreamBerdError::parse("Expected `}` to close this block").with_span(start))
             }
         }
         Some(Token::Space(_)) => inner_parse(tokens),
         Some(Token::LParen) => {
-            let val = get_tuple(tokens)?;
+            let val = get_list(tokens, start, &Token::RParen)?;
             if let [x] = &val[..] {
                 Ok(x.clone())
             } else {
-                Ok(Syntax::Block(val))
+                Ok(Syntax::Block(val, start.to(tokens.last_span())))
             }
         }
-        Some(other) => Err(format!("Unexpected token `{other:?}`")),
-        None => Err(String::from("Unexpected End of File")),
+        Some(Token::LSquare) => {
+            let items = get_list(tokens, start, &Token::RSquare)?;
+            Ok(Syntax::Array(items, start.to(tokens.last_span())))
+        }
+        Some(other) => {
+            Err(DreamBerdError::parse(format!("Unexpected token `{other:?}`")).with_span(start))
+        }
+        None => Err(DreamBerdError::parse("Unexpected End of File").with_span(start)),
     }
 }
 
-fn consume_whitespace<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> u8 {
-    let mut sp = 0;
+/// parse the expression inside of a string interpolation; `at` is the span of the string
+fn interpolation(tokens: Vec<(Token, Span)>, at: Span) -> SResult<Syntax> {
+    if tokens.iter().all(|(tok, _)| matches!(tok, Token::Space(_))) {
+        return Err(
+            DreamBerdError::parse("Expected an expression inside of this interpolation")
+                .with_span(at),
+        );
+    }
+    // the string is already finished, so more code can't fix what's inside of it
+    parse(tokens).map_err(|mut err| {
+        err.incomplete = false;
+        err
+    })
+}
+
+fn consume_whitespace(tokens: &mut Tokens) -> u8 {
+    let mut sp = 0u8;
     while let Some(&Token::Space(s)) = tokens.peek() {
         tokens.next();
-        sp += s;
+        sp = sp.saturating_add(s);
     }
     sp
 }
 
-fn consume_bang<T: Iterator<Item = Token>>(syn: Syntax, tokens: &mut Peekable<T>) -> Syntax {
+fn consume_bang(syn: Syntax, tokens: &mut Tokens) -> Syntax {
     match tokens.peek() {
         Some(&Token::Bang(q)) => {
             tokens.next();
-            Syntax::Statement(false, Box::new(syn), q)
+            let span = syn.span().to(tokens.last_span());
+            Syntax::Statement(false, Box::new(syn), q, span)
         }
         Some(&Token::Question(q)) => {
             tokens.next();
-            Syntax::Statement(true, Box::new(syn), q)
+            let span = syn.span().to(tokens.last_span());
+            Syntax::Statement(true, Box::new(syn), q, span)
         }
         _ => syn,
     }
 }
 
-fn declare<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>, id: &str) -> SResult<Syntax> {
+#[allow(clippy::too_many_lines)]
+fn declare(tokens: &mut Tokens, id: &str, start: Span) -> SResult<Syntax> {
     let Some(Token::Ident(second)) = tokens.next() else {
-                    return Err(String::from("Expected `const` or `var` after `{id}`"))
-                };
+        return Err(
+            DreamBerdError::parse(format!("Expected `const` or `var` after `{id}`"))
+                .with_span(tokens.last_span()),
+        );
+    };
     let var_type = match (id, second.as_ref()) {
         ("var", "var") => VarType::VarVar,
         ("var", "const") => VarType::VarConst,
         ("const", "var") => VarType::ConstVar,
         ("const", "const") => VarType::ConstConst,
         ("var" | "const", _) => {
-            return Err(format!(
+            return Err(DreamBerdError::parse(format!(
                 "Expected `const` or `var` after `{id}`, not `{second}`"
             ))
+            .with_span(tokens.last_span()))
         }
         _ => unreachable!(),
     };
     consume_whitespace(tokens);
-    let Some(Token::Ident(varname)) = tokens.next() else {
-                    return Err(format!("Expected a variable name after `{id} {second}`"))
-                };
+    // `{name, health: hp}` declares variables from the fields of an object
+    let mut fields = None;
+    let varname = match tokens.next() {
+        Some(Token::LSquirrely) => {
+            fields = Some(destructure_fields(tokens)?);
+            Rc::from("")
+        }
+        // numbers are names too, so they can be redefined
+        Some(Token::Ident(varname) | Token::Number(varname)) => varname,
+        _ => {
+            return Err(DreamBerdError::parse(format!(
+                "Expected a variable name after `{id} {second}`"
+            ))
+            .with_span(tokens.last_span()))
+        }
+    };
     consume_whitespace(tokens);
     // get a lifetime
     let lifetime = match tokens.peek() {
         Some(Token::LCaret) => {
             tokens.next();
             match tokens.next() {
-                Some(Token::Ident(ident)) => {
+                Some(Token::Ident(ident) | Token::Number(ident)) => {
                     let lt = Lifetime::Ticks(ident.parse().map_err(|err| {
-                        format!("Expected integer lifetime; got `{ident}`; {err}")
+                        DreamBerdError::parse(format!(
+                            "Expected integer lifetime; got `{ident}`; {err}"
+                        ))
+                        .with_span(tokens.last_span())
                     })?);
                     match tokens.next() {
                         Some(Token::RCaret) => {}
                         Some(other) => {
-                            return Err(format!(
+                            return Err(DreamBerdError::parse(format!(
                                 "Expected `>` after lifetime value; got `{other:?}`"
                             ))
+                            .with_span(tokens.last_span()))
+                        }
+                        None => {
+                            return Err(DreamBerdError::parse("Unexpected EOF")
+                                .with_span(tokens.last_span()))
                         }
-                        None => return Err(String::from("Unexpected EOF")),
                     }
                     lt
                 }
-                Some(other) => return Err(format!("Expected integer lifetime; got `{other:?}`")),
-                None => return Err(String::from("Unexpected EOF")),
+                Some(other) => {
+                    return Err(DreamBerdError::parse(format!(
+                        "Expected integer lifetime; got `{other:?}`"
+                    ))
+                    .with_span(tokens.last_span()))
+                }
+                None => {
+                    return Err(
+                        DreamBerdError::parse("Unexpected EOF").with_span(tokens.last_span())
+                    )
+                }
             }
         }
         _ => Lifetime::Default,
     };
     consume_whitespace(tokens);
-    // consume a type definition
-    if tokens.peek() == Some(&Token::Colon) {
+    // get a type annotation
+    let annotation = if fields.is_none() && tokens.peek() == Some(&Token::Colon) {
         tokens.next();
+        let annotation = get_type(tokens)?;
         consume_whitespace(tokens);
-        get_type(tokens)?;
-        consume_whitespace(tokens);
-    }
+        Some(annotation)
+    } else {
+        None
+    };
     let value = match tokens.next() {
-        Some(Token::Bang(_)) => Syntax::Ident(String::new().into()),
+        Some(Token::Bang(_)) if fields.is_some() => {
+            return Err(DreamBerdError::parse(
+                "Expected `=` after the fields; there's nothing to take them out of",
+            )
+            .with_span(tokens.last_span()))
+        }
+        Some(Token::Bang(_)) => Syntax::Ident(String::new().into(), tokens.last_span()),
         Some(Token::Equal(1)) => {
             consume_whitespace(tokens);
-            grouping::parse_group::<T>(tokens)?
+            grouping::parse_group(tokens)?
         }
         other => {
-            return Err(format!(
+            return Err(DreamBerdError::parse(format!(
                 "Expected `!`, `:`, or `=` after variable name, got `{other:?}`"
             ))
+            .with_span(tokens.last_span()))
         }
     };
-    Ok(consume_bang(
-        Syntax::Declare(var_type, varname, lifetime, Box::new(value)),
-        tokens,
-    ))
+    let span = start.to(value.span());
+    let declaration = match fields {
+        Some(fields) => Syntax::Destructure(var_type, fields, lifetime, Box::new(value), span),
+        None => Syntax::Declare(
+            var_type,
+            varname,
+            lifetime,
+            annotation,
+            Box::new(value),
+            span,
+        ),
+    };
+    Ok(consume_bang(declaration, tokens))
+}
+
+/// parse the rest of `export name to "file"`, after `export`
+fn export(tokens: &mut Tokens, start: Span) -> SResult<Syntax> {
+    consume_whitespace(tokens);
+    let Some(Token::Ident(name) | Token::Number(name)) = tokens.next() else {
+        return Err(
+            DreamBerdError::parse("Expected the name of a variable after `export`")
+                .with_span(tokens.last_span()),
+        );
+    };
+    consume_whitespace(tokens);
+    if !matches!(tokens.next(), Some(Token::Ident(to)) if to.as_ref() == "to") {
+        return Err(
+            DreamBerdError::parse(format!("Expected `to` after `export {name}`"))
+                .with_span(tokens.last_span()),
+        );
+    }
+    consume_whitespace(tokens);
+    let target = inner_parse(tokens)?;
+    let span = start.to(target.span());
+    Ok(Syntax::Export(name, Box::new(target), span))
+}
+
+/// parse the fields after the `{` of a destructuring declaration, like `name, health: hp}`.
+/// Each field comes with the name of the variable it goes in.
+fn destructure_fields(tokens: &mut Tokens) -> SResult<Vec<(Rc<str>, Rc<str>)>> {
+    let start = tokens.last_span();
+    let mut fields = Vec::new();
+    loop {
+        consume_whitespace(tokens);
+        let field = match tokens.next() {
+            Some(Token::RSquirrely) => break,
+            Some(Token::Ident(field) | Token::Number(field)) => field,
+            Some(other) => {
+                return Err(DreamBerdError::parse(format!(
+                    "Expected the name of a field; got `{other:?}`"
+                ))
+                .with_span(tokens.last_span()))
+            }
+            None => {
+                return Err(DreamBerdError::parse("Expected `}` to close these fields")
+                    .with_span(start)
+                    .incomplete())
+            }
+        };
+        consume_whitespace(tokens);
+        // `field: name` puts the field in a variable with a different name
+        let name = if tokens.peek() == Some(&Token::Colon) {
+            tokens.next();
+            consume_whitespace(tokens);
+            match tokens.next() {
+                Some(Token::Ident(name) | Token::Number(name)) => name,
+                _ => {
+                    return Err(DreamBerdError::parse(format!(
+                        "Expected a variable name after `{field}:`"
+                    ))
+                    .with_span(tokens.last_span()))
+                }
+            }
+        } else {
+            field.clone()
+        };
+        fields.push((field, name));
+        consume_whitespace(tokens);
+        match tokens.next() {
+            Some(Token::Comma) => {}
+            Some(Token::RSquirrely) => break,
+            Some(other) => {
+                return Err(DreamBerdError::parse(format!(
+                    "Expected `,` or `}}` after a field; got `{other:?}`"
+                ))
+                .with_span(tokens.last_span()))
+            }
+            None => {
+                return Err(DreamBerdError::parse("Expected `}` to close these fields")
+                    .with_span(start)
+                    .incomplete())
+            }
+        }
+    }
+    Ok(fields)
 }
 
-fn get_tuple<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> SResult<Vec<Syntax>> {
+/// parse the rest of a comma-separated list that ends with `close`; `start` is the span of the bracket that opened it
+fn get_list(tokens: &mut Tokens, start: Span, close: &Token) -> SResult<Vec<Syntax>> {
     let mut args_buf = Vec::new();
-    while let Some(tok) = tokens.peek() {
-        match tok {
-            Token::Comma => {
+    loop {
+        match tokens.peek() {
+            Some(Token::Comma) => {
                 tokens.next();
                 consume_whitespace(tokens);
             }
-            Token::RParen => {
+            Some(tok) if tok == close => {
                 tokens.next();
                 break;
             }
-            _ => args_buf.push(grouping::parse_group::<T>(tokens)?),
+            None => {
+                let bracket = if *close == Token::RSquare { ']' } else { ')' };
+                return Err(DreamBerdError::parse(format!(
+                    "Expected `{bracket}` to close this group"
+                ))
+                .with_span(start)
+                .incomplete());
+            }
+            _ => args_buf.push(grouping::parse_group(tokens)?),
         }
     }
     Ok(args_buf)
 }
 
-fn get_type<T: Iterator<Item = Token>>(tokens: &mut Peekable<T>) -> SResult<()> {
-    match tokens.next() {
-        Some(Token::Ident(_)) => {}
-        other => return Err(format!("Expected a type after `:`; got `{other:?}`")),
-    }
+fn get_type(tokens: &mut Tokens) -> SResult<Type> {
     consume_whitespace(tokens);
-    match tokens.peek() {
-        Some(Token::LSquare) => {
-            tokens.next();
-            let Some(Token::RSquare) = tokens.next() else {
-                return Err(String::from("Expected `]` after `[` in type definition"))
-            };
+    let name = match tokens.next() {
+        Some(Token::Ident(name)) => name,
+        other => {
+            return Err(DreamBerdError::parse(format!(
+                "Expected a type after `:`; got `{other:?}`"
+            ))
+            .with_span(tokens.last_span()))
         }
-        Some(Token::LCaret) => {
+    };
+    consume_whitespace(tokens);
+    let mut typ = if tokens.peek() == Some(&Token::LCaret) {
+        tokens.next();
+        let mut generics = vec![get_type(tokens)?];
+        while tokens.peek() == Some(&Token::Comma) {
             tokens.next();
-            get_type(tokens)?;
-            while tokens.peek() == Some(&Token::Comma) {
-                tokens.next();
-                get_type(tokens)?;
-                consume_whitespace(tokens);
-            }
-            let Some(Token::RCaret) = tokens.next() else {
-                return Err(String::from("Missing `>` in type definition"))
-            };
+            generics.push(get_type(tokens)?);
+            consume_whitespace(tokens);
         }
-        _ => {}
+        let Some(Token::RCaret) = tokens.next() else {
+            return Err(DreamBerdError::parse("Missing `>` in type definition")
+                .with_span(tokens.last_span()));
+        };
+        Type::Named(name, generics)
+    } else {
+        Type::Named(name, Vec::new())
+    };
+    while tokens.peek() == Some(&Token::LSquare) {
+        tokens.next();
+        let Some(Token::RSquare) = tokens.next() else {
+            return Err(
+                DreamBerdError::parse("Expected `]` after `[` in type definition")
+                    .with_span(tokens.last_span()),
+            );
+        };
+        typ = Type::Array(Box::new(typ));
     }
-    Ok(())
+    Ok(typ)
 }
 
 fn optimize(syn: Syntax) -> Syntax {
-    match syn {
-        Syntax::Declare(typ, ident, lifetime, inner) => {
-            Syntax::Declare(typ, ident, lifetime, Box::new(optimize(*inner)))
-        }
-        Syntax::Function(args, inner) => Syntax::Function(args, Box::new(optimize(*inner))),
-        Syntax::UnaryOperation(UnaryOperation::Call(args), func) => Syntax::UnaryOperation(
-            UnaryOperation::Call(args.into_iter().map(optimize).collect()),
-            Box::new(optimize(*func)),
-        ),
-        Syntax::Operation(lhs, Operation::Dot, rhs)
-            if 'guard: {
-                let Syntax::Ident(ref lhs) = *lhs else { break 'guard false };
-                let Syntax::Ident(ref rhs) = *rhs else { break 'guard false };
-                format!("{lhs}.{rhs}").parse::<f64>().is_ok()
-            } =>
-        {
-            let Syntax::Ident(lhs) = *lhs else {panic!()};
-            let Syntax::Ident(rhs) = *rhs else {panic!()};
-            let Ok(float) = format!("{lhs}.{rhs}").parse::<f32>() else { panic!()};
-            Syntax::Ident(format!("{float}").into())
-        }
-        Syntax::Operation(lhs, op, rhs) => {
-            Syntax::Operation(Box::new(optimize(*lhs)), op, Box::new(optimize(*rhs)))
-        }
-        Syntax::UnaryOperation(unary, operand) => {
-            Syntax::UnaryOperation(unary, Box::new(optimize(*operand)))
-        }
-        Syntax::Block(inner) => {
-            let mut new_inner: Vec<_> = Vec::with_capacity(inner.len());
-            // flatten nested blocks
-            for item in inner {
-                match optimize(item) {
-                    Syntax::Block(block) => new_inner.extend(block),
-                    other => new_inner.push(other),
+    Optimizer.fold_syntax(syn)
+}
+
+/// simplifies blocks, from the inside out
+struct Optimizer;
+
+impl Fold for Optimizer {
+    fn fold_syntax(&mut self, syn: Syntax) -> Syntax {
+        match fold_children(self, syn) {
+            Syntax::Block(inner, span) => {
+                let mut new_inner: Vec<_> = Vec::with_capacity(inner.len());
+                // flatten nested blocks
+                for item in inner {
+                    match item {
+                        Syntax::Block(block, _) => new_inner.extend(block),
+                        other => new_inner.push(other),
+                    }
+                }
+                // replace a block containing a single element with that element
+                if new_inner.len() == 1 {
+                    new_inner.pop().unwrap()
+                } else {
+                    Syntax::Block(new_inner, span)
                 }
             }
-            // replace a block containing a single element with that element
-            if new_inner.len() == 1 {
-                new_inner.pop().unwrap()
-            } else {
-                Syntax::Block(new_inner)
-            }
-        }
-        Syntax::Statement(is_debug, inner, lvl) => {
-            Syntax::Statement(is_debug, Box::new(optimize(*inner)), lvl)
+            other => other,
         }
-        basic @ (Syntax::Ident(_) | Syntax::String(_)) => basic,
     }
 }
@@ -2,23 +2,55 @@ use crate::types::prelude::*;
 
 use std::{f64::consts as f64, fmt::Display};
 
+/// run `syntax` with every engine, and make sure that they agree
+fn interpret(syntax: &Syntax, config: &Config) -> SResult<Pointer> {
+    let tree = crate::interpreter::interpret(
+        syntax,
+        Config {
+            engine: Engine::Tree,
+            ..config.clone()
+        },
+    );
+    let vm = crate::interpreter::interpret(
+        syntax,
+        Config {
+            engine: Engine::Vm,
+            ..config.clone()
+        },
+    );
+    assert_eq!(
+        format!("{:?}", tree.as_ref().map(Pointer::clone_inner)),
+        format!("{:?}", vm.as_ref().map(Pointer::clone_inner)),
+        "the engines disagree on `{syntax}`"
+    );
+    tree
+}
+
 fn eval<T: Display>(src: T) -> SResult<Value> {
-    Ok(
-        crate::interpreter::interpret(&crate::parser::parse(crate::lexer::tokenize(&format!(
-            "{{{src}}}"
-        ))?)?)?
-        .clone_inner(),
-    )
+    eval_with(src, &Config::default())
+}
+
+/// put code in a block to run it, on a line of its own so that a comment at the end can't take the `}` too
+fn wrap<T: Display>(src: T) -> String {
+    format!("{{{src}\n}}")
+}
+
+fn eval_with<T: Display>(src: T, config: &Config) -> SResult<Value> {
+    Ok(interpret(
+        &crate::parser::parse(crate::lexer::tokenize(&wrap(src))?)?,
+        config,
+    )?
+    .clone_inner())
 }
 
 macro_rules! assert_eq_db {
     ($lhs: expr, $rhs: expr) => {
-        let ltoks = crate::lexer::tokenize(&format!("{{{}}}", $lhs)).unwrap();
-        let rtoks = crate::lexer::tokenize(&format!("{{{}}}", $rhs)).unwrap();
+        let ltoks = crate::lexer::tokenize(&wrap($lhs)).unwrap();
+        let rtoks = crate::lexer::tokenize(&wrap($rhs)).unwrap();
         let lsyn = crate::parser::parse(ltoks).unwrap();
         let rsyn = crate::parser::parse(rtoks).unwrap();
-        let lres = crate::interpreter::interpret(&lsyn).unwrap().clone_inner();
-        let rres = crate::interpreter::interpret(&rsyn).unwrap().clone_inner();
+        let lres = interpret(&lsyn, &Config::default()).unwrap().clone_inner();
+        let rres = interpret(&rsyn, &Config::default()).unwrap().clone_inner();
         assert_eq!(lres, rres, "{lsyn:?} != {rsyn:?}")
     };
 }
@@ -93,8 +125,26 @@ fn op_assign() {
 fn function() {
     assert_eq!(
         eval("const const does_she_really_like_you = () -> maybe! does_she_really_like_you"),
-        Ok(Value::Function(Vec::new(), Syntax::Ident("maybe".into())))
+        Ok(Value::Function(
+            Vec::new(),
+            std::rc::Rc::new(Syntax::Ident("maybe".into(), Span::default())),
+            Closure::default()
+        ))
     );
+    // every function that's made from the same code shares its body, instead of getting a copy of it
+    let Ok(Value::Array(made)) = eval("const const make = ()->{()->{1}}!\n[make(), make()]") else {
+        panic!()
+    };
+    let bodies: Vec<_> = made
+        .iter()
+        .map(|func| {
+            func.with_ref(|func| match func {
+                Value::Function(_, body, _) => body.clone(),
+                other => panic!("{other:?}"),
+            })
+        })
+        .collect();
+    assert!(std::rc::Rc::ptr_eq(&bodies[0], &bodies[1]));
 }
 
 #[test]
@@ -147,6 +197,19 @@ fn string_interpolation() {
         "const const name = `John`! `Hi, I'm {name}€`",
         "`Hi, I'm John`"
     );
+    assert_eq_db!("const const a = 2! `${a + 1} £{a*a}`", "`3 4`");
+    assert_eq_db!("const const double = x -> x*2! `{double(21)}円`", "`42`");
+    assert_eq_db!("`${ {`}`} }`", "`}`");
+    assert_eq_db!(
+        "const var p = {}! p.hp = 7! `${p.hp} {p$hp} {p.hp * 2}₽`",
+        "`7 7 14`"
+    );
+    assert_eq_db!("const const a = 1! `{a is ${a}}`", "`{a is 1}`");
+    let err = eval("`${}`").unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Parse);
+    let err = eval("`${a b +}`").unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Parse);
+    assert_eq!(eval("`${(`").unwrap_err().kind, ErrorKind::Lex);
 }
 
 #[test]
@@ -154,5 +217,1041 @@ fn eval_tests() {
     assert_eq_db!("eval(2)", "2");
     assert_eq_db!("const var x = 1! x += 2! eval(`x`)", "3");
     assert_eq_db!("const var x = 1! x += 2! eval(x)", "3");
-    assert_eq_db!("const const x = `'Hello, World!'`! eval(x)", "`Hello, World!`");
+    assert_eq_db!(
+        "const const x = `'Hello, World!'`! eval(x)",
+        "`Hello, World!`"
+    );
+}
+
+#[test]
+fn spans() {
+    let tokens = crate::lexer::tokenize("x = 5!\n  print(x)!").unwrap();
+    let (Token::Ident(print), span) = &tokens[7] else {
+        panic!("{tokens:?}")
+    };
+    assert_eq!(print.as_ref(), "print");
+    assert_eq!((span.start, span.end, span.line, span.col), (9, 14, 2, 3));
+
+    let err = eval("const const x = 5!\n  x = 6!").unwrap_err();
+    assert!(err.to_string().starts_with("2:3: "), "{err}");
+    let err = eval("const const x = 5!\n\n    (1 2))!").unwrap_err();
+    assert!(err.to_string().starts_with("3:10: "), "{err}");
+}
+
+#[test]
+fn errors() {
+    let err = eval("const const x = 5!\n  x = 6!").unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Assignment);
+    assert_eq!(eval("if(true)").unwrap_err().kind, ErrorKind::Arity);
+    assert_eq!(eval("(1 2))").unwrap_err().kind, ErrorKind::Parse);
+    assert_eq!(eval("`unterminated").unwrap_err().kind, ErrorKind::Lex);
+
+    let source = "const const x = 5!\nconst const f = () -> {\n    x = 6!\n}!\nf()!";
+    let err = crate::parser::parse(crate::lexer::tokenize(source).unwrap())
+        .and_then(|syn| interpret(&syn, &Config::default()))
+        .unwrap_err();
+    assert_eq!(
+        err.render(source, "main.db"),
+        "error[assignment]: Can't assign to a `const const` const const (5)
+ --> main.db:3:5
+  |
+3 |     x = 6!
+  |     ^^^^^
+  = stack trace:
+      0: f at main.db:5:1
+"
+    );
+    let err = eval("eval(`1 + (`)").unwrap_err();
+    assert_eq!(err.notes.len(), 1, "{err:?}");
+}
+
+#[test]
+fn comments() {
+    assert_eq_db!("1 + 2*3 // seven", "7");
+    assert_eq!(eval("1 + 2*3 // seven"), Ok(Value::from(7.0)));
+    assert_eq_db!("// nothing to see here\n1+2 * 3", "9");
+    assert_eq_db!("1 + /* one */ 2*3", "7");
+    assert_eq_db!("1 + 2 /* two */ * 3", "1 + 2 * 3");
+    assert_eq_db!("1+2/* /* nested */ comment */ * 3", "9");
+    assert_eq_db!(
+        "const var count = 0! // start at zero\ncount += 1! /* add one */ count",
+        "1"
+    );
+    assert_eq_db!("`// not a comment`", "'// not a comment'");
+    assert_eq!(
+        crate::lexer::tokenize("/* /* */").unwrap_err().kind,
+        ErrorKind::Lex
+    );
+}
+
+#[test]
+fn escapes() {
+    assert_eq!(
+        eval(r#""a\nb\tc\rd\0e\\f""#),
+        Ok(Value::from("a\nb\tc\rd\0e\\f"))
+    );
+    assert_eq!(eval(r#""\u{1F967}""#), Ok(Value::from("🥧")));
+    assert_eq!(eval(r#""\"\'\`\«\»\„\“""#), Ok(Value::from("\"'`«»„“")));
+    assert_eq!(eval(r"«\«\»»"), Ok(Value::from("«»")));
+    assert_eq!(eval(r"„\“\„“"), Ok(Value::from("“„")));
+    assert_eq!(
+        eval(r"const const name = `John`! `\${name} is \£{name} and \{name}€`"),
+        Ok(Value::from("${name} is £{name} and {name}€"))
+    );
+    let err = eval(r#""\q""#).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Lex);
+    assert_eq!(eval(r#""\u{110000}""#).unwrap_err().kind, ErrorKind::Lex);
+}
+
+#[test]
+fn lossless_cst() {
+    let cst = |src: &str| {
+        crate::parser::cst::parse_cst(src, crate::lexer::tokenize_lossless(src).unwrap()).unwrap()
+    };
+    for src in [
+        "const const name: String = «world»!!\nprint(`Hello ${name}`)?? // greet\n",
+        "1+2 * 3?   /* a /* nested */ comment */  \n\n  x -> {x*x}!",
+        "var var<2> list: Int[] = [1, 2,   3]!\r\nfunction(a,b)=>{ a + b }",
+        "print „quoted\\n“!\n\t{ 1 } ",
+        "",
+    ] {
+        assert_eq!(cst(src).to_string(), src);
+    }
+    let Cst::File(children, _) = cst("x = 1!! // one\n{ y! }") else {
+        panic!()
+    };
+    assert!(
+        matches!(
+            &children[..],
+            [
+                Cst::Statement(..),
+                Cst::Token(Token::Space(_), ..),
+                Cst::Comment(..),
+                Cst::Token(Token::Space(_), ..),
+                Cst::Statement(..)
+            ]
+        ),
+        "{children:?}"
+    );
+    let err = crate::parser::cst::parse_cst("(]", crate::lexer::tokenize_lossless("(]").unwrap())
+        .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Parse);
+}
+
+#[test]
+fn strict_types() {
+    let strict = || Config {
+        strict_types: true,
+        ..Config::default()
+    };
+    // annotations do nothing by default
+    assert_eq!(
+        eval("const const x: i32 = `five`! x"),
+        Ok(Value::from("five"))
+    );
+    assert_eq!(
+        eval_with("const const x: i32 = 5! x", &strict()),
+        Ok(Value::from(5.0))
+    );
+    let err = eval_with("const const x: i32 = `five`!", &strict()).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Runtime);
+    assert!(
+        err.message.contains("`i32`") && err.message.contains("`String`"),
+        "{err:?}"
+    );
+    assert!(eval_with("const const x: u8 = ;5!", &strict()).is_err());
+    // sized integers have to fit
+    assert!(eval_with("const const x: u8 = 255!", &strict()).is_ok());
+    let err = eval_with("const const x: u8 = 300!", &strict()).unwrap_err();
+    assert_eq!(err.message, "`x` is declared as `u8`, but got `300`");
+    assert!(eval_with("const const x: u8 = ;1!", &strict()).is_err());
+    assert!(eval_with("const const x: i8 = ;128!", &strict()).is_ok());
+    assert!(eval_with("const const x: i8 = 1000!", &strict()).is_err());
+    assert!(eval_with("const const x: i32 = 2.5!", &strict()).is_err());
+    assert!(eval_with("const const x: Int = 1000!", &strict()).is_ok());
+    assert!(eval_with("const const x: String = 5!", &strict()).is_err());
+    assert!(eval_with("const const x: Option<String> = undefined!", &strict()).is_ok());
+    assert!(eval_with("const const x: T = 2.5!", &strict()).is_ok());
+    assert!(eval_with("const const list: T[] = {}!", &strict()).is_ok());
+    assert!(eval_with("const const f: Fn<i32, i32> = (a, b) -> a!", &strict()).is_ok());
+    assert!(eval_with("const const f: Fn<i32> = 1!", &strict()).is_err());
+    // function parameters
+    assert_eq!(
+        eval_with(
+            "const const f = (a: i32, b: String) -> b*a! f(2, `ab`)",
+            &strict()
+        ),
+        Ok(Value::from("abab"))
+    );
+    assert!(eval_with("const const f = (a: i32) -> a! f(`one`)", &strict()).is_err());
+    assert!(eval_with("function(f, (a: i32), a)! f(1.5)", &strict()).is_err());
+    assert!(eval_with("const const f = (a: i32 = `one`)->a! f()", &strict()).is_err());
+    assert!(eval_with("const const f = (a: i32 = `one`)->a! f(1)", &strict()).is_ok());
+    // annotated assignments
+    assert!(eval_with("const var o = {}! o.x: String = 1!", &strict()).is_err());
+    assert_eq!(
+        eval_with("const var o = {}! o.x: String = `1`! o.x", &strict()),
+        Ok(Value::from("1"))
+    );
+}
+
+#[test]
+fn typecheck() {
+    let check = |src: &str| {
+        crate::typecheck::typecheck(
+            &crate::parser::parse(crate::lexer::tokenize(src).unwrap()).unwrap(),
+        )
+    };
+    for src in [
+        "const const x: i32 = 5!",
+        "const const x: f64 = 5!",
+        "const const x: Option<String> = undefined!",
+        "const const mul: Fn<i32, i32> = (lhs: i32, rhs: i32)->{lhs * rhs}! mul(2, 3)!",
+        "const const apply: Fn<Fn<T>, T> = (f: Fn<T>, x: T)->f(x)! apply((n: i32)->n, 1)!",
+        "const const id: Fn<T> = (t: T)->t! id(1)! id(`one`)!",
+        "const var list: T[] = {}!",
+        "const var name: String = `a`! name = name + 1!",
+        "const const f = (a: i32, b: i32 = 2)->{a + b}! f(1)! f(b = 3, a = 1)!",
+        include_str!("../examples/std.db"),
+        include_str!("../examples/linked_list.db"),
+    ] {
+        let errors = check(src);
+        assert!(errors.is_empty(), "{src}: {errors:?}");
+    }
+    for src in [
+        "const const x: i32 = `five`!",
+        "const const x: u8 = 2.5!",
+        "const const x: String = undefined!",
+        "const const f: Fn<i32> = `f`!",
+        "const const apply: Fn<Fn<i32, i32>> = (f: Fn<i32, i32>)->f(1, 2)! apply(`add`)!",
+        "const const apply: Fn<Fn<i32>> = (f: Fn<i32>)->f(1)! apply((s: String)->s)!",
+        "const const pair: Fn<T, T> = (a: T, b: T)->a! pair(1, `one`)!",
+        "const var age: i32 = 5! age = `old`!",
+        "const const f = (n: i32)->{const const s: String = n!}!",
+        "function(f, (a: Boolean), a)! f(1)!",
+        "const const f = (a: i32 = `one`)->a!",
+    ] {
+        let errors = check(src);
+        assert_eq!(errors.len(), 1, "{src}: {errors:?}");
+        assert_eq!(errors[0].kind, ErrorKind::Type);
+    }
+    // the examples that come with DreamBerd are all fine
+    let examples = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/examples")).unwrap();
+    for path in examples.map(|entry| entry.unwrap().path()) {
+        if path.extension().is_some_and(|ext| ext == "db") {
+            let errors = check(&std::fs::read_to_string(&path).unwrap());
+            assert!(errors.is_empty(), "{}: {errors:?}", path.display());
+        }
+    }
+}
+
+#[test]
+fn incremental() {
+    use crate::parser::{Incremental, Parsed};
+
+    let mut code = Incremental::default();
+    assert_eq!(
+        code.push("const const f = (a, b) -> {\n"),
+        Ok(Parsed::Incomplete)
+    );
+    assert_eq!(code.push("  a +\n"), Ok(Parsed::Incomplete));
+    assert_eq!(code.push("  b\n"), Ok(Parsed::Incomplete));
+    assert!(matches!(code.push("}!\n"), Ok(Parsed::Complete(_))));
+    for unfinished in [
+        "`unterminated",
+        "print(1,",
+        "/* comment",
+        "`${name`",
+        "\"\\u{1F9",
+        "const const x =",
+        "const var",
+        "1 + ",
+        "{ x! ",
+    ] {
+        assert_eq!(
+            Incremental::default().push(unfinished),
+            Ok(Parsed::Incomplete),
+            "{unfinished}"
+        );
+    }
+    for invalid in ["(]", "x }", "\"\\q", "var nope = 1!", "\"${1 +}\""] {
+        let err = Incremental::default().push(invalid).unwrap_err();
+        assert!(!err.incomplete, "{invalid}: {err:?}");
+    }
+    // more text can continue the last token
+    let mut code = Incremental::default();
+    assert_eq!(code.push("const const long_na"), Ok(Parsed::Incomplete));
+    assert!(matches!(
+        code.push("me = 1! long_name"),
+        Ok(Parsed::Complete(_))
+    ));
+    let Ok(Parsed::Complete(syntax)) = code.push("") else {
+        panic!()
+    };
+    assert_eq!(
+        interpret(&syntax, &Config::default()).map(|ptr| ptr.clone_inner()),
+        Ok(Value::from(1.0))
+    );
+    assert!(Incremental::default().finish().is_ok());
+    let mut code = Incremental::default();
+    code.push("(1 + 2").unwrap();
+    assert_eq!(code.finish().unwrap_err().kind, ErrorKind::Parse);
+    // statements that are finished aren't parsed again, but everything comes out the same as parsing it all at once
+    let lines = [
+        "const var x = 1!\n",
+        "x += 1!\n",
+        "const const f = (a) -> {\n",
+        "  a + x\n",
+        "}!\n",
+        "f(1)!\n",
+        "===== other.db =====\n",
+        "x!\n",
+    ];
+    let mut code = Incremental::default();
+    let mut finished = Vec::new();
+    for line in lines {
+        let parsed = code.push(line);
+        let source = code.source().to_string();
+        let whole = crate::parser::parse(crate::lexer::tokenize(&source).unwrap());
+        match parsed {
+            Ok(Parsed::Complete(syntax)) => assert_eq!(Ok(syntax), whole, "{source}"),
+            other => assert_eq!(other, Ok(Parsed::Incomplete), "{source}"),
+        }
+        finished.push(code.finished.len());
+    }
+    assert_eq!(finished, [1, 2, 2, 2, 3, 4, 4, 4]);
+    let mut code = Incremental::default();
+    assert_eq!(
+        code.push("x!"),
+        Ok(Parsed::Complete(
+            crate::parser::parse(crate::lexer::tokenize("x!").unwrap()).unwrap()
+        ))
+    );
+    // the last `!` can still get longer
+    assert!(code.finished.is_empty());
+    let Ok(Parsed::Complete(Syntax::Statement(_, _, 2, _))) = code.push("!") else {
+        panic!()
+    };
+}
+
+#[test]
+fn numbers() {
+    // this used to go through an `f32`
+    assert_eq!(eval("3.141592653589793"), Ok(Value::from(f64::PI)));
+    assert_eq!(eval("1_000_000"), Ok(Value::from(1_000_000.0)));
+    assert_eq!(eval("0xff + 0b101 + 0o17"), Ok(Value::from(275.0)));
+    assert_eq!(eval("6.02e23"), Ok(Value::from(6.02e23)));
+    assert_eq!(eval("2.5e-3"), Ok(Value::from(0.0025)));
+    assert_eq!(eval("1e+2"), Ok(Value::from(100.0)));
+    // however a number is written, it's the same name
+    assert_eq_db!("const const 5 = 4!\n5", "4");
+    assert_eq_db!("const const 16 = 4!\n0x10 + 5.0", "9");
+    // words that only start with a digit are still identifiers
+    assert_eq_db!("2nd", "\"2nd\"");
+    assert_eq_db!("1_", "\"1_\"");
+    assert_eq!(
+        crate::lexer::tokenize("1.5.x").unwrap()[..3],
+        [
+            (Token::Number("1.5".into()), Span::new(0, 3, 1, 1)),
+            (Token::Dot, Span::new(3, 4, 1, 4)),
+            (Token::Ident("x".into()), Span::new(4, 5, 1, 5)),
+        ]
+    );
+}
+
+#[test]
+fn arrays() {
+    let array = |items: &[f64]| Value::Array(items.iter().map(|&num| num.into()).collect());
+    assert_eq!(eval("[1, 2, 3]"), Ok(array(&[1.0, 2.0, 3.0])));
+    assert_eq!(eval("[]"), Ok(array(&[])));
+    // arrays start at -1
+    assert_eq_db!("[4, 5, 6][;1]", "4");
+    assert_eq_db!("[4, 5, 6][1]", "6");
+    assert_eq_db!("[4, 5, 6][2]", "undefined");
+    assert_eq_db!("[4, 5, 6][0.5]", "undefined");
+    assert_eq_db!("const const a = [[1, 2], [3]]!\na[;1][0]", "2");
+    assert_eq_db!("const const f = x -> [x, x * 2]!\nf(3)[0]", "6");
+    assert_eq_db!("1+[1, 2][0]", "3");
+    assert_eq!(
+        eval("const var a = [1, 2, 3]!\na[0.5] = 9!\na[;1] = 0!\na"),
+        Ok(array(&[0.0, 2.0, 9.0, 3.0]))
+    );
+    assert_eq!(
+        eval("const var a = [1]!\na[;1.5] = 0!\na[1] = 3!\na"),
+        Ok(array(&[0.0, 1.0, 3.0]))
+    );
+    assert_eq!(
+        eval("const var a = [1]!\npush(a, 2)!\npush(a, 3)!\npop(a)!\na"),
+        Ok(array(&[1.0, 2.0]))
+    );
+    assert_eq_db!("const var a = [1]!\na[1] = 2!\na", "[1, undefined, 2]");
+    assert_eq_db!("const var a = [1, 5]!\npop(a)", "5");
+    assert_eq_db!("len([1, 2, 3])", "3");
+    assert_eq_db!("len(\"hello\")", "5");
+    // arrays keep their own copies of values
+    assert_eq!(
+        eval("var var x = 1!\nconst const a = [x]!\nx = 2!\na"),
+        Ok(array(&[1.0]))
+    );
+    assert!(eval("const const a = [1]!\npush(a, 2)!").is_err());
+    assert!(eval("const var a = [1]!\na[;2] = 2!").is_err());
+    // indexes that would need a huge gap filled in, or that aren't anywhere, are errors instead of crashes
+    for idx in ["1e300", "1e12", "infinity", ";infinity"] {
+        let err = eval(format!("const var a = [1]!\na[{idx}] = 2!")).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Runtime, "`a[{idx}] = 2`");
+    }
+    // arrays with things in them that don't have an order can't be ordered either
+    assert_eq_db!("[{}] < [{}]", "false");
+    assert_eq_db!("[{}] > [{}]", "false");
+    assert_eq_db!("[1, {}] < [2, {}]", "true");
+    assert_eq!(
+        eval("[1, \"two\", [3]]").map(|val| format!("{val}")),
+        Ok(String::from("[1, two, [3]]"))
+    );
+    assert_eq!(
+        eval("[1, \"two\"]").map(|val| format!("{val:?}")),
+        Ok(String::from("[1, \"two\"]"))
+    );
+}
+
+#[test]
+fn recovery() {
+    let source = "const const a = 1!
+const b = 2!
+print(1 +)!
+if (a, {
+    const const = 3!
+    a?
+})!
+}
+var var c = a + 2!
+c?";
+    let (syntax, errors) = crate::parser::parse_recovering(crate::lexer::tokenize(source).unwrap());
+    let lines: Vec<_> = errors
+        .iter()
+        .map(|err| (err.kind, err.span.map(|span| span.line)))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            (ErrorKind::Parse, Some(2)),
+            (ErrorKind::Parse, Some(3)),
+            (ErrorKind::Parse, Some(5)),
+            (ErrorKind::Parse, Some(8)),
+        ]
+    );
+    let Syntax::Block(statements, _) = &syntax else {
+        panic!("{syntax:?}");
+    };
+    assert!(matches!(statements[1], Syntax::Error(_)));
+    assert!(matches!(statements[2], Syntax::Error(_)));
+    // the rest of the block with an error in it is still there
+    let Syntax::Statement(_, call, ..) = &statements[3] else {
+        panic!("{:?}", statements[3]);
+    };
+    let Syntax::UnaryOperation(UnaryOperation::Call(args), ..) = &**call else {
+        panic!("{call:?}");
+    };
+    let Some(Syntax::Block(body, _)) = args.get(1) else {
+        panic!("{args:?}");
+    };
+    assert!(matches!(
+        body[..],
+        [Syntax::Error(_), Syntax::Statement(true, ..)]
+    ));
+    assert_eq!(
+        statements.last().map(ToString::to_string),
+        Some(String::from("c?"))
+    );
+    // the normal parser still stops at the first error
+    let err = crate::parser::parse(crate::lexer::tokenize(source).unwrap()).unwrap_err();
+    assert_eq!(err.span.map(|span| span.line), Some(2));
+    // code without errors parses the same way either way
+    let source = "const const a = 1!\na + 2?";
+    assert_eq!(
+        crate::parser::parse_recovering(crate::lexer::tokenize(source).unwrap()),
+        (
+            crate::parser::parse(crate::lexer::tokenize(source).unwrap()).unwrap(),
+            Vec::new()
+        )
+    );
+}
+
+#[test]
+fn power_floor_div_and_in() {
+    assert_eq_db!("2^10", "1024");
+    assert_eq_db!("9^0.5", "3");
+    assert_eq_db!("2^3^2", "64");
+    assert_eq_db!("true^false", "true");
+    assert_eq_db!("true^true", "false");
+    assert_eq_db!("false^maybe", "maybe");
+    assert_eq_db!("7//2", "3");
+    assert_eq_db!(";7//2", ";4");
+    assert_eq_db!("1//0", "undefined");
+    assert_eq_db!("\"a\"^2", "undefined");
+    assert_eq_db!("var var x = 7!\nx //= 2!\nx ^= 2!\nx", "9");
+    assert_eq_db!("const var x = 7!\nx ^= 2!\nx", "49");
+    assert_eq_db!("var const x = 7!\nx ^= 2!\nx", "7");
+    assert_eq_db!("3 in [1, 2, 3]", "true");
+    assert_eq_db!("4 in [1, 2, 3]", "false");
+    assert_eq_db!("\"ell\" in \"hello\"", "true");
+    assert_eq_db!("const var o = {}!\no.a = 1!\n\"a\" in o", "true");
+    assert_eq_db!("const var o = {}!\n\"a\" in o", "false");
+    // `//` is only floor division when it's squished between two values
+    assert_eq_db!("7 // 2", "7");
+    assert_eq_db!("(7)//2", "3");
+    assert_eq_db!("7! //2\n8", "8");
+    // even when the right side looks like a comment, so comments after a value need a space
+    assert_eq_db!("const const x = 7!\nconst const note = 2!\nx//note", "3");
+    assert_eq_db!("const const x = 7!\nx //note", "7");
+    assert_eq_db!("const const x = 7!\nx // note", "7");
+    assert_eq!(eval("const const x = 7!\nx // note"), Ok(Value::from(7.0)));
+    // a comment can't hide the end of the statement before it
+    let err = eval("7 // 2?\n1").unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Lex);
+    assert_eq!(err.span, Some(Span::new(3, 8, 1, 4)));
+    assert_eq_db!("7//2", "3");
+    assert_eq_db!("const const y = 7! // seven?\ny", "7");
+    assert_eq_db!("print(7)! // all done!\n7", "7");
+    let toks = |source| {
+        crate::lexer::tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|(tok, _)| tok)
+            .filter(|tok| !matches!(tok, Token::Space(_)))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        toks("x //= 2"),
+        [
+            Token::Ident("x".into()),
+            Token::SlashSlashEq,
+            Token::Number("2".into())
+        ]
+    );
+    assert_eq!(toks("x // == 2"), [Token::Ident("x".into())]);
+    // the lexer picks up from the character right before where it stopped
+    let mut lexer = crate::lexer::ResumableLexer::default();
+    lexer.push("7/").unwrap();
+    assert_eq!(
+        lexer.push("/2").unwrap().to_vec(),
+        crate::lexer::tokenize("7//2").unwrap()
+    );
+}
+
+#[test]
+fn early_return() {
+    let sign = "const const sign = n -> {\n    if(n < 0, {\n        return \"negative\"!\n    })!\n    if(n ==== 0, return \"zero\")!\n    \"positive\"\n}!\n";
+    assert_eq_db!(format!("{sign}sign(;3)"), "\"negative\"");
+    assert_eq_db!(format!("{sign}sign(0)"), "\"zero\"");
+    assert_eq_db!(format!("{sign}sign(3)"), "\"positive\"");
+    assert_eq_db!(
+        "const const f = () -> {\n    return!\n    1\n}!\nf()",
+        "undefined"
+    );
+    assert_eq_db!(
+        "const const f = () -> {\n    return()!\n    1\n}!\nf()",
+        "undefined"
+    );
+    // only the nearest function returns
+    assert_eq_db!(
+        "const const f = () -> {\n    const const g = () -> { return 1! 2 }!\n    g() + 10\n}!\nf()",
+        "11"
+    );
+    // `return` in a method returns from the method
+    assert_eq_db!("const var o = {}!\no.call = ()->{ return 5! 6 }!\no()", "5");
+    // at the top level, it ends the program
+    assert_eq_db!("var var x = 1!\nreturn x!\nx = 2!\nx", "1");
+    assert_eq_db!("\"code\" + eval(\"return 3!\")!\n4", "3");
+    assert_eq!(
+        eval("return(1, 2)!").map_err(|err| err.kind),
+        Err(ErrorKind::Arity)
+    );
+}
+
+#[test]
+fn tail_calls() {
+    // this would overflow the stack if every call needed its own frame
+    assert_eq_db!(
+        "const const sum = (n, total) -> {\n    if(n ==== 0, total, sum(n - 1, total + n))\n}!\nsum(20000, 0)",
+        "200010000"
+    );
+    assert_eq_db!(
+        "var var i = 0!\nconst const f = () -> {\n    i += 1!\n    if(i < 20000, {\n        f()!\n    })!\n}!\nf()!\ni",
+        "20000"
+    );
+    // a helper that's tail called can still see the scope it was made in
+    assert_eq_db!(
+        "const const f = n -> {\n    const const go = (i, total)->{if(i ==== 0, total, go(i - 1, total + n))}!\n    go(n, 0)\n}!\nf(3)",
+        "9"
+    );
+    // a call that's a statement still returns undefined
+    assert_eq_db!(
+        "const const g = () -> 5!\nconst const f = () -> {\n    g()!\n}!\nf()",
+        "undefined"
+    );
+    assert_eq_db!(
+        "const const f = n -> {\n    if(n ==== 0, {\n        return \"done\"!\n    })!\n    f(n - 1)\n}!\nf(20000)",
+        "\"done\""
+    );
+    // with dynamic scope, a call at the end still runs inside of whatever called it
+    let dynamic = Config {
+        dynamic_scope: true,
+        ..Config::default()
+    };
+    let shadowed = |call| {
+        format!("const const x = 1!\nconst const f = ()->{{x}}!\nconst const g = ()->{{\n    const const x = 2!\n    {call}\n}}!\ng()")
+    };
+    assert_eq!(eval_with(shadowed("f()"), &dynamic), Ok(Value::from(2.0)));
+    assert_eq!(
+        eval_with(shadowed("f() + 0"), &dynamic),
+        Ok(Value::from(2.0))
+    );
+    // and it can still loop forever without the scopes piling up
+    assert_eq!(
+        eval_with(
+            "const const one = 1!\nconst const sum = (n, total) -> {\n    const const next = total + one!\n    if(n ==== 0, total, sum(n - 1, next))\n}!\nsum(20000, 0)",
+            &dynamic
+        ),
+        Ok(Value::from(20000.0))
+    );
+}
+
+#[test]
+fn default_and_named_args() {
+    let f = "const const f = (a, b = 2)->{a*10 + b}!\n";
+    assert_eq_db!(format!("{f}f(1)"), "12");
+    assert_eq_db!(format!("{f}f(1, 3)"), "13");
+    assert_eq_db!(format!("{f}f(b = 5, a = 4)"), "45");
+    assert_eq_db!(format!("{f}f(7, b = 1)"), "71");
+    assert_eq_db!(format!("{f}f()"), "undefined");
+    // defaults are evaluated on every call, and they can use the parameters before them
+    assert_eq_db!(
+        "var var n = 1!\nconst const g = (a, b = a+n)->{b}!\ng(1)!\nn = 5!\ng(1)",
+        "6"
+    );
+    assert_eq_db!("function(h, (x, y: Int = 4), x+y)!\nh(1)", "5");
+    assert_eq_db!("fn(h, n = 3, n*2)!\nh()", "6");
+    assert_eq_db!("union(h, (x, y), x - y)!\nh(y = 1, x = 3)", "2");
+    assert_eq_db!("const const h = (n = 3)->{n}!\nh()", "3");
+    // an assignment to something that isn't a parameter is just an argument
+    assert_eq_db!(
+        format!("{f}var var x = 0!\nconst const y = f(x = 9)!\n[x, y]"),
+        "[9, 92]"
+    );
+    let kind = |src: String| eval(src).map_err(|err| err.kind);
+    assert_eq!(kind(format!("{f}f(1, 2, 3)")), Err(ErrorKind::Arity));
+    assert_eq!(kind(format!("{f}f(1, a = 2)")), Err(ErrorKind::Arity));
+    let Ok(Value::Function(params, ..)) = eval("(a, b: Int = 2)->{a}") else {
+        panic!()
+    };
+    assert_eq!(Param::list(&params), "a, b: Int = 2");
+}
+
+#[test]
+fn rest_and_arguments() {
+    let f = "const const f = (first, ...rest)->{[first, rest]}!\n";
+    assert_eq_db!(format!("{f}f(1)"), "[1, []]");
+    assert_eq_db!(format!("{f}f(1, 2, 3)"), "[1, [2, 3]]");
+    assert_eq_db!(format!("{f}f()"), "[undefined, []]");
+    assert_eq_db!("fn(g, ...xs, len(xs))!\ng(1, 2)", "2");
+    assert_eq_db!(
+        "const const sum = (...nums)->{\n    const const go = (idx, total)->{\n        if(idx >= len(nums)-1, total, go(idx+1, total + nums[idx]))\n    }!\n    go(;1, 0)\n}!\nsum(1, 2, 3, 4)",
+        "10"
+    );
+    // `arguments` has everything that was passed, in order
+    let g = "const const g = (a, b = 5)->{arguments}!\n";
+    assert_eq_db!(format!("{g}g(1)"), "[1]");
+    assert_eq_db!(format!("{g}g(b = 2, a = 1)"), "[2, 1]");
+    assert_eq_db!("const const h = ()->{arguments}!\nh()", "[]");
+    let Ok(Value::Function(params, ..)) = eval("(a, ...b)->{a}") else {
+        panic!()
+    };
+    assert_eq!(Param::list(&params), "a, ...b");
+    let kind = |src: &str| {
+        crate::lexer::tokenize(src)
+            .and_then(crate::parser::parse)
+            .and_then(|syntax| interpret(&syntax, &Config::default()))
+            .map_err(|err| err.kind)
+    };
+    assert_eq!(
+        kind("const const f = (...a, b)->{b}!").map(|_| ()),
+        Err(ErrorKind::Parse)
+    );
+    assert_eq!(
+        kind("function(f, (...a, b), b)!").map(|_| ()),
+        Err(ErrorKind::Runtime)
+    );
+    assert_eq!(kind("...a").map(|_| ()), Err(ErrorKind::Runtime));
+}
+
+#[test]
+fn destructuring() {
+    let player = "class(Player, {\n    const var health = 10!\n    const const name = `Samuel`!\n})!\nconst var player = new Player!\n";
+    assert_eq_db!(
+        format!("{player}const const {{name, health}} = player!\n[name, health]"),
+        "[`Samuel`, 10]"
+    );
+    assert_eq_db!(
+        format!("{player}const const {{name: n, missing}} = player!\n[n, missing]"),
+        "[`Samuel`, undefined]"
+    );
+    assert_eq_db!(
+        format!("{player}const const {{\n    name: first,\n    health\n}} = player!\nfirst"),
+        "`Samuel`"
+    );
+    // every name gets the same kind of declaration
+    assert_eq_db!(
+        format!("{player}var var {{name}} = player!\nname = `Bob`!\nname"),
+        "`Bob`"
+    );
+    assert_eq_db!(
+        format!("{player}const var {{health}} = player!\nhealth += 1!\nhealth"),
+        "11"
+    );
+    assert!(eval(format!(
+        "{player}const const {{name}} = player!\nname = `Bob`!"
+    ))
+    .is_err());
+    assert!(eval(format!(
+        "{player}var const {{health}} = player!\nhealth = 1!"
+    ))
+    .is_ok());
+    // and the same lifetime
+    assert_eq_db!(
+        format!("{player}const const {{health}}<1> = player!\n1!\nhealth"),
+        "undefined"
+    );
+    let syntax = crate::parser::parse(
+        crate::lexer::tokenize("const var {name, health: hp}<2> = player!").unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        syntax.to_string(),
+        "const var {name, health: hp}<2> = player!"
+    );
+    for invalid in [
+        "const const {name} !",
+        "const const {name: !} = player!",
+        "const const {name health} = player!",
+    ] {
+        let result = crate::lexer::tokenize(invalid).and_then(crate::parser::parse);
+        assert_eq!(
+            result.map_err(|err| err.kind),
+            Err(ErrorKind::Parse),
+            "{invalid}"
+        );
+    }
+}
+
+#[test]
+fn multiple_files() {
+    let run = |src: &str| -> SResult<Value> {
+        Ok(interpret(
+            &crate::parser::parse(crate::lexer::tokenize(src)?)?,
+            &Config::default(),
+        )?
+        .clone_inner())
+    };
+    let tokens = crate::lexer::tokenize("1!\n===== add.db =====\n2!\n=====\n").unwrap();
+    assert!(tokens.contains(&(
+        Token::FileBreak(Some("add.db".into())),
+        Span::new(3, 21, 2, 1)
+    )));
+    assert!(tokens.iter().any(|(tok, _)| *tok == Token::FileBreak(None)));
+    // it has to be a whole line of its own
+    assert!(!crate::lexer::tokenize("1 ===== 1")
+        .unwrap()
+        .iter()
+        .any(|(tok, _)| matches!(tok, Token::FileBreak(_))));
+    let add = "const const add = (a, b)->{a + b}!\n";
+    assert_eq!(
+        run(&format!(
+            "===== add.db =====\n{add}export add to \"main.db\"!\n\n======= main.db =======\nadd(3, 2)"
+        )),
+        Ok(Value::from(5.0))
+    );
+    // each file has its own variables
+    assert_eq!(run(&format!("{add}=====\nadd")), Ok(Value::from("add")));
+    assert_eq!(
+        run(&format!(
+            "{add}export add to \"b.db\"!\n===== a.db =====\nadd\n===== b.db =====\nadd(1, 1)"
+        )),
+        Ok(Value::from(2.0))
+    );
+    assert_eq!(
+        run(&format!(
+            "{add}export add to \"b.db\"!\n===== a.db =====\nadd\n===== b.db =====\nadd(1, 1)\n===== a.db =====\nadd"
+        )),
+        Ok(Value::from("add"))
+    );
+    // `return` only ends the file it's in
+    assert_eq!(run("return(1)!\n=====\n2"), Ok(Value::from(2.0)));
+    assert!(run("export add!").is_err());
+    assert!(run("export add from \"b.db\"!").is_err());
+    // the files are checked separately too
+    let errors = crate::typecheck::typecheck(
+        &crate::parser::parse(
+            crate::lexer::tokenize(
+                "const const n: Int = 1!\nexport n to \"b.db\"!\n===== b.db =====\nconst const s: String = n!",
+            )
+            .unwrap(),
+        )
+        .unwrap(),
+    );
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn visitors() {
+    use crate::types::visit::{fold_children, Fold, Visitor, VisitorMut};
+    struct Idents(Vec<String>);
+    impl Visitor for Idents {
+        fn visit_ident(&mut self, name: &std::rc::Rc<str>, _: Span) {
+            self.0.push(name.to_string());
+        }
+    }
+    struct Rename;
+    impl VisitorMut for Rename {
+        fn visit_ident_mut(&mut self, name: &mut std::rc::Rc<str>, _: Span) {
+            if name.as_ref() == "a" {
+                *name = "b".into();
+            }
+        }
+    }
+    struct Double;
+    impl Fold for Double {
+        fn fold_syntax(&mut self, syn: Syntax) -> Syntax {
+            match fold_children(self, syn) {
+                Syntax::Ident(num, span) if num.as_ref() == "1" => Syntax::Ident("2".into(), span),
+                other => other,
+            }
+        }
+    }
+    let parse =
+        |source: &str| crate::parser::parse(crate::lexer::tokenize(source).unwrap()).unwrap();
+    let idents = |syn: &Syntax| {
+        let mut idents = Idents(Vec::new());
+        idents.visit_syntax(syn);
+        idents.0
+    };
+    // every identifier that's used is visited, wherever it is
+    assert_eq!(
+        idents(&parse("const const f = (x = a)->{`${b}`}!\nf(c)?")),
+        ["a", "b", "f", "c"]
+    );
+    let mut syn = parse("const const f = (x = a)->{a + `${a}`}!\nf(a)");
+    Rename.visit_syntax_mut(&mut syn);
+    assert_eq!(idents(&syn), ["b", "b", "b", "f", "b"]);
+    assert_eq!(
+        interpret(
+            &Double.fold_syntax(parse("[1, ((x = 1)->{x})(), `${1}`]")),
+            &Config::default()
+        )
+        .unwrap()
+        .clone_inner(),
+        eval("[2, 2, `2`]").unwrap()
+    );
+    // `when` watches the variables anywhere in its condition
+    assert_eq_db!(
+        "const var x = 1!\nvar var seen = 0!\nwhen({const const y = x! y > 1}, {seen = 1!})!\nx += 1!\nseen",
+        "1"
+    );
+    assert_eq_db!(
+        "const var x = 1!\nvar var seen = 0!\nwhen(((n)->{n + x})(0) > 1, {seen = 1!})!\nx += 1!\nseen",
+        "1"
+    );
+}
+
+#[test]
+fn closures() {
+    let counter = "const const make = ()->{\n    const var count = 0!\n    ()->{\n        count += 1!\n        count\n    }\n}!\nconst const counter = make()!\n";
+    assert_eq_db!(format!("{counter}counter()!\ncounter()"), "2");
+    assert_eq_db!(
+        "const const adder = a->{b->{a + b}}!\nconst const add1 = adder(1)!\nadd1(2)",
+        "3"
+    );
+    // the function sees the `x` from where it was made, not from where it was called
+    let shadowed = "const const x = 1!\nconst const f = ()->{x}!\nconst const g = ()->{\n    const const x = 2!\n    f() + 0\n}!\ng()";
+    assert_eq!(eval(shadowed), Ok(Value::from(1.0)));
+    let dynamic = Config {
+        dynamic_scope: true,
+        ..Config::default()
+    };
+    assert_eq!(eval_with(shadowed, &dynamic), Ok(Value::from(2.0)));
+    assert_eq!(
+        eval_with(
+            "const const adder = a->{b->{a + b}}!\nconst const add1 = adder(1)!\nadd1(2)",
+            &dynamic
+        ),
+        eval("`a2`")
+    );
+    // a returned function still sees everything in the scope it was made in, even what's declared after it
+    assert_eq_db!(
+        "const const make = ()->{\n    const const f = ()->{g()}!\n    const const g = ()->{5}!\n    f\n}!\nconst const h = make()!\nh()",
+        "5"
+    );
+    assert_eq_db!(
+        "const const mk = ()->{\n    const const down = n->{if(n <= 0, 0, down(n-1) + 1)}!\n    down\n}!\nconst const f = mk()!\nf(3)",
+        "3"
+    );
+    // a function kept in the scope it was made in is let go of along with it once nothing else can use them
+    let state = rc_mut_new(State::new());
+    // each statement runs right in `state`, like in the REPL
+    let run = |src: &str| {
+        let statements = match crate::parser::parse(crate::lexer::tokenize(src).unwrap()).unwrap() {
+            Syntax::Block(statements, _) => statements,
+            other => vec![other],
+        };
+        let mut last = Ok(Value::empty_object());
+        for statement in statements {
+            last = crate::interpreter::run(&statement, state.clone()).map(|ptr| ptr.clone_inner());
+        }
+        last
+    };
+    let Ok(Value::Function(_, _, closure)) = run(
+        "const const mk = ()->{\n    const const inner: Function = ()->{1}!\n    inner\n}!\nconst const f = mk()!\nf",
+    ) else {
+        panic!("`f` should be a function");
+    };
+    let scope = std::rc::Rc::downgrade(closure.made_in().unwrap());
+    drop(closure);
+    state.borrow().scopes().borrow_mut().collect();
+    assert_eq!(run("f()").ok(), Some(Value::from(1.0)));
+    run("forget f!").unwrap();
+    assert!(scope.upgrade().is_some());
+    state.borrow().scopes().borrow_mut().collect();
+    assert!(scope.upgrade().is_none());
+}
+
+#[test]
+fn stack_traces() {
+    let src = "const const inner = x->{\n    x()\n}!\nfn(middle, (x), inner(x) + 1)!\nconst const outer = ()->{\n    middle(5) + 1\n}!\nouter()!";
+    let err = eval(src).unwrap_err();
+    let names: Vec<_> = err.stack.iter().map(Frame::name).collect();
+    assert_eq!(names, ["inner", "middle", "outer"]);
+    let rendered = err.render(&format!("{{{src}}}"), "main.db");
+    assert!(rendered.contains("= stack trace:\n      0: inner at main.db:4:17\n"));
+    // a call at the end of a function takes its place in the stack
+    assert_eq!(
+        eval("const const f = ()->{stack()}!\nconst const g = ()->{f()}!\ng()"),
+        eval("[`f at 2:22`]")
+    );
+    assert_eq!(
+        eval(
+            "const const f = ()->{stack()}!\nconst const g = f!\n(()->{const const s = g()! s})()"
+        ),
+        eval("[`f at 3:23`, `<anonymous> at 3:2`]")
+    );
+    assert_eq!(eval("stack()"), Ok(Value::Array(Vec::new())));
+    assert!(eval("5()").unwrap_err().stack.is_empty());
+}
+
+#[test]
+fn max_depth() {
+    let shallow = Config {
+        max_depth: 20,
+        ..Config::default()
+    };
+    let count = "const const count = n->{if(n <= 0, 0, count(n-1) + 1)}!\n";
+    assert_eq!(
+        eval_with(format!("{count}count(19)"), &shallow),
+        Ok(Value::from(19.0))
+    );
+    let err = eval_with(format!("{count}count(20)"), &shallow).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::Runtime);
+    assert_eq!(err.stack.len(), 20);
+    // calls at the end of a function don't count
+    assert_eq!(
+        eval_with(
+            "const const count = (n, total)->{if(n <= 0, total, count(n-1, total + 1))}!\ncount(100, 0)",
+            &shallow
+        ),
+        Ok(Value::from(100.0))
+    );
+    // everything is still fine afterwards, like in the REPL
+    for engine in [Engine::Tree, Engine::Vm] {
+        let state = rc_mut_new(State::with_config(Config {
+            engine,
+            ..shallow.clone()
+        }));
+        let run = |src: &str| {
+            let syntax = crate::parser::parse(crate::lexer::tokenize(src).unwrap()).unwrap();
+            crate::interpreter::run(&syntax, state.clone())
+        };
+        assert!(run("const const f = ()->{f() + 1}!\nf()").is_err());
+        assert_eq!(
+            run("stack()").map(|ptr| ptr.clone_inner()).ok(),
+            Some(Value::Array(Vec::new()))
+        );
+    }
+    // the limit is reached before the stack runs out, even on a thread with the usual amount of it
+    let deep = std::thread::Builder::new()
+        .stack_size(2 * 1024 * 1024)
+        .spawn(move || {
+            let run = |n| {
+                eval(format!("{count}count({n})"))
+                    .map(|val| val.to_string())
+                    .map_err(|err| err.kind)
+            };
+            (run(999), run(5000))
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(deep, (Ok("999".into()), Err(ErrorKind::Runtime)));
+}
+
+#[test]
+fn budget() {
+    let fuel = |fuel| Config {
+        fuel: Some(fuel),
+        ..Config::default()
+    };
+    let forever = "const const f = ()->{f()}!\nf()";
+    assert_eq!(
+        eval_with(forever, &fuel(1000)).unwrap_err().kind,
+        ErrorKind::OutOfFuel
+    );
+    assert_eq!(
+        eval_with("const var x = 1!\nx += 2!\nx", &fuel(10)),
+        Ok(Value::from(3.0))
+    );
+    assert_eq!(
+        eval_with("const var x = 1!\nx += 2!\nx", &fuel(1))
+            .unwrap_err()
+            .kind,
+        ErrorKind::OutOfFuel
+    );
+    for engine in [Engine::Tree, Engine::Vm] {
+        // how long it takes to run out of time isn't the same each time, so the engines are run on their own
+        let syntax = crate::parser::parse(crate::lexer::tokenize(forever).unwrap()).unwrap();
+        let config = Config {
+            engine,
+            timeout: Some(std::time::Duration::from_millis(10)),
+            ..Config::default()
+        };
+        assert_eq!(
+            crate::interpreter::interpret(&syntax, config)
+                .unwrap_err()
+                .kind,
+            ErrorKind::Timeout
+        );
+        // running out leaves the state alone, so it can keep going once it's refilled
+        let state = rc_mut_new(State::with_config(Config {
+            engine,
+            ..fuel(100)
+        }));
+        let run = |src: &str| {
+            let syntax = crate::parser::parse(crate::lexer::tokenize(src).unwrap()).unwrap();
+            crate::interpreter::run(&syntax, state.clone())
+        };
+        assert!(run("const const x = 5!").is_ok());
+        assert!(run(forever).is_err());
+        state.borrow().budget.refill();
+        assert_eq!(
+            run("x").map(|ptr| ptr.clone_inner()).ok(),
+            Some(Value::from(5.0))
+        );
+    }
 }
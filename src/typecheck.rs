@@ -0,0 +1,476 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::types::prelude::*;
+
+/// Check the type annotations in a program without running it, and get every mismatch
+pub fn typecheck(syn: &Syntax) -> Vec<DreamBerdError> {
+    let mut checker = Checker {
+        scopes: vec![HashMap::new()],
+        exports: HashMap::new(),
+        errors: Vec::new(),
+    };
+    checker.infer(syn, None);
+    checker.errors
+}
+
+struct Checker {
+    /// the declared or inferred type of each variable, innermost scope last
+    scopes: Vec<HashMap<Rc<str>, Type>>,
+    /// the types of the variables that have been exported to each file
+    exports: HashMap<Rc<str>, Vec<(Rc<str>, Type)>>,
+    errors: Vec<DreamBerdError>,
+}
+
+impl Checker {
+    fn lookup(&self, name: &str) -> Option<Type> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    fn bind(&mut self, name: Rc<str>, typ: Type) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, typ);
+        }
+    }
+
+    fn scoped<T>(&mut self, func: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let res = func(self);
+        self.scopes.pop();
+        res
+    }
+
+    /// report an error if `actual` doesn't fit `expected`
+    fn expect(
+        &mut self,
+        expected: &Type,
+        actual: Option<&Type>,
+        span: Span,
+        what: impl FnOnce() -> String,
+    ) {
+        let Some(actual) = actual else {
+            return;
+        };
+        if !fits(actual, expected, &mut HashMap::new()) {
+            self.errors.push(
+                DreamBerdError::new(
+                    ErrorKind::Type,
+                    format!("{} should be `{expected}`, but it's `{actual}`", what()),
+                )
+                .with_span(span),
+            );
+        }
+    }
+
+    /// Get the type of an expression, if we can tell what it is, and check everything inside of it.
+    /// `expected` gives unannotated function parameters their types.
+    fn infer(&mut self, syn: &Syntax, expected: Option<&Type>) -> Option<Type> {
+        match syn {
+            Syntax::Ident(name, _) => self.lookup(name).or_else(|| literal_type(name)),
+            Syntax::Error(_) => None,
+            Syntax::String(segments, _) => {
+                for segment in segments {
+                    if let StringSegment::Interpolation(inner) = segment {
+                        self.infer(inner, None);
+                    }
+                }
+                Some(base("String"))
+            }
+            Syntax::Array(items, _) => {
+                let expected_item = match expected {
+                    Some(Type::Array(item)) => Some(&**item),
+                    _ => None,
+                };
+                let types: Vec<Option<Type>> = items
+                    .iter()
+                    .map(|item| self.infer(item, expected_item))
+                    .collect();
+                // an array's items all have one type, if we can tell what it is
+                let item_type = match types.split_first() {
+                    Some((Some(first), rest))
+                        if rest.iter().all(|typ| typ.as_ref() == Some(first)) =>
+                    {
+                        first.clone()
+                    }
+                    _ => base("_"),
+                };
+                Some(Type::Array(Box::new(item_type)))
+            }
+            Syntax::Block(statements, _) => self.scoped(|this| {
+                let mut last = None;
+                for statement in statements {
+                    last = this.infer(statement, None);
+                }
+                last
+            }),
+            Syntax::Statement(is_debug, inner, ..) => {
+                let typ = self.infer(inner, None);
+                typ.filter(|_| *is_debug)
+            }
+            Syntax::Declare(_, name, _, annotation, value, span) => {
+                let actual = self.infer(value, annotation.as_ref());
+                if let Some(annotation) = annotation {
+                    self.expect(annotation, actual.as_ref(), *span, || format!("`{name}`"));
+                }
+                if let Some(typ) = annotation.clone().or(actual) {
+                    self.bind(name.clone(), typ);
+                }
+                None
+            }
+            Syntax::Destructure(_, fields, _, value, _) => {
+                self.infer(value, None);
+                // we don't know the types of fields
+                for (_, name) in fields {
+                    self.bind(name.clone(), base("_"));
+                }
+                None
+            }
+            Syntax::File(name, body, _) => {
+                // each file starts out with only what's been exported to it
+                let imports = name
+                    .as_ref()
+                    .and_then(|name| self.exports.remove(name))
+                    .unwrap_or_default();
+                let outer =
+                    std::mem::replace(&mut self.scopes, vec![imports.into_iter().collect()]);
+                let typ = self.infer(body, None);
+                self.scopes = outer;
+                typ
+            }
+            Syntax::Export(name, target, _) => {
+                self.infer(target, None);
+                // we can only tell which file it goes to if it's spelled out
+                if let Syntax::String(segments, _) = &**target {
+                    if let [StringSegment::String(file)] = segments.as_slice() {
+                        let typ = self.lookup(name).unwrap_or_else(|| base("_"));
+                        self.exports
+                            .entry(file.clone())
+                            .or_default()
+                            .push((name.clone(), typ));
+                    }
+                }
+                None
+            }
+            Syntax::Annotated(inner, annotation, span) => {
+                let actual = self.infer(inner, Some(annotation));
+                self.expect(annotation, actual.as_ref(), *span, || format!("`{inner}`"));
+                Some(annotation.clone())
+            }
+            Syntax::Function(params, body, _) => Some(self.function(params, body, expected)),
+            Syntax::UnaryOperation(UnaryOperation::Call(args), func, _) => self.call(func, args),
+            Syntax::UnaryOperation(UnaryOperation::Negate, inner, _) => {
+                self.infer(inner, None).map(|typ| match base_of(&typ) {
+                    // negating a positive number makes it negative
+                    Some(BaseType::UInt) => base("Int"),
+                    _ => typ,
+                })
+            }
+            Syntax::UnaryOperation(_, inner, _) => self.infer(inner, None),
+            Syntax::Operation(lhs, op, rhs, span) => self.operation(lhs, *op, rhs, *span),
+        }
+    }
+
+    fn operation(&mut self, lhs: &Syntax, op: Operation, rhs: &Syntax, span: Span) -> Option<Type> {
+        if op == Operation::Dot {
+            // we don't know the types of fields
+            self.infer(lhs, None);
+            return None;
+        }
+        if op == Operation::Index {
+            self.infer(rhs, None);
+            return match self.infer(lhs, None) {
+                Some(Type::Array(item)) if !is_generic(&item) => Some(*item),
+                _ => None,
+            };
+        }
+        let declared = match (op, lhs) {
+            (Operation::Equal(1), Syntax::Ident(name, _)) => self.lookup(name),
+            (Operation::Equal(1), Syntax::Annotated(_, annotation, _)) => Some(annotation.clone()),
+            _ => None,
+        };
+        let lhs_type = match lhs {
+            // the annotation is checked against the new value, not the old one
+            Syntax::Annotated(inner, ..) => self.infer(inner, None),
+            lhs => self.infer(lhs, None),
+        };
+        let rhs_type = self.infer(rhs, declared.as_ref());
+        if let Some(declared) = declared {
+            self.expect(&declared, rhs_type.as_ref(), span, || format!("`{lhs}`"));
+            return rhs_type;
+        }
+        let lhs_base = lhs_type.as_ref().and_then(base_of);
+        let rhs_base = rhs_type.as_ref().and_then(base_of);
+        match (op, lhs_base, rhs_base) {
+            (
+                Operation::Equal(_)
+                | Operation::Lt
+                | Operation::Le
+                | Operation::Gt
+                | Operation::Ge
+                | Operation::And
+                | Operation::Or
+                | Operation::In,
+                ..,
+            ) => Some(base("Boolean")),
+            (Operation::Add, Some(BaseType::String), _)
+            | (Operation::Add, _, Some(BaseType::String)) => Some(base("String")),
+            (Operation::Mul, Some(BaseType::String), Some(numeric)) if is_numeric(numeric) => {
+                Some(base("String"))
+            }
+            (
+                Operation::Add | Operation::Sub | Operation::Mul | Operation::Mod,
+                Some(lhs),
+                Some(rhs),
+            ) if is_numeric(lhs) && is_numeric(rhs) => Some(base(match (lhs, rhs) {
+                (BaseType::UInt, BaseType::UInt) if op != Operation::Sub => "UInt",
+                (BaseType::Int | BaseType::UInt, BaseType::Int | BaseType::UInt) => "Int",
+                _ => "Number",
+            })),
+            (Operation::Div, Some(lhs), Some(rhs)) if is_numeric(lhs) && is_numeric(rhs) => {
+                Some(base("Number"))
+            }
+            (Operation::FloorDiv, Some(lhs), Some(rhs)) if is_numeric(lhs) && is_numeric(rhs) => {
+                Some(base(match (lhs, rhs) {
+                    (BaseType::UInt, BaseType::UInt) => "UInt",
+                    _ => "Int",
+                }))
+            }
+            // a negative power makes a fraction, so only `UInt` and `Int` bases keep their type
+            (Operation::Pow, Some(lhs), Some(rhs)) if is_numeric(lhs) && is_numeric(rhs) => {
+                Some(base(match (lhs, rhs) {
+                    (BaseType::UInt, BaseType::UInt) => "UInt",
+                    (BaseType::Int, BaseType::UInt) => "Int",
+                    _ => "Number",
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    fn function(&mut self, params: &[Param], body: &Syntax, expected: Option<&Type>) -> Type {
+        let expected_params = match expected {
+            Some(Type::Named(name, params))
+                if BaseType::from_name(name) == Some(BaseType::Function) =>
+            {
+                params.as_slice()
+            }
+            _ => &[],
+        };
+        let param_types: Vec<Type> = params
+            .iter()
+            .enumerate()
+            .map(|(idx, param)| {
+                param
+                    .annotation
+                    .clone()
+                    .or_else(|| expected_params.get(idx).cloned())
+                    .unwrap_or_else(|| base("_"))
+            })
+            .collect();
+        self.scoped(|this| {
+            for (param, typ) in params.iter().zip(&param_types) {
+                if let Some(default) = &param.default {
+                    let actual = this.infer(default, Some(typ));
+                    this.expect(typ, actual.as_ref(), default.span(), || {
+                        format!("the default for `{}`", param.name)
+                    });
+                }
+                this.bind(param.name.clone(), typ.clone());
+            }
+            this.infer(body, None);
+        });
+        // `Fn` types can't say how many arguments `...rest` takes, so it's left out
+        let param_types = params
+            .iter()
+            .zip(param_types)
+            .filter(|(param, _)| !param.rest)
+            .map(|(_, typ)| typ)
+            .collect();
+        Type::Named("Fn".into(), param_types)
+    }
+
+    fn call(&mut self, func: &Syntax, args: &[Syntax]) -> Option<Type> {
+        match (func, args) {
+            // `function(name, args, body)` declares a function
+            (Syntax::Ident(keyword, _), [Syntax::Ident(name, span), params, body])
+                if keyword.as_ref() == "function" && self.lookup(keyword).is_none() =>
+            {
+                let params = match params {
+                    Syntax::Block(params, _) => {
+                        params.iter().filter_map(Param::from_syntax).collect()
+                    }
+                    other => Param::from_syntax(other).into_iter().collect(),
+                };
+                let func = Syntax::Function(params, Rc::new(body.clone()), *span);
+                if let Some(typ) = self.infer(&func, None) {
+                    self.bind(name.clone(), typ);
+                }
+                return None;
+            }
+            // class bodies are their own scope
+            (Syntax::Ident(keyword, _), [Syntax::Ident(..), body])
+                if matches!(keyword.as_ref(), "class" | "className")
+                    && self.lookup(keyword).is_none() =>
+            {
+                self.infer(body, None);
+                return None;
+            }
+            _ => {}
+        }
+        let func_type = self.infer(func, None);
+        let params = match &func_type {
+            Some(Type::Named(name, params))
+                if BaseType::from_name(name) == Some(BaseType::Function) =>
+            {
+                params.as_slice()
+            }
+            _ => &[],
+        };
+        // generics are the same across all of the arguments of one call
+        let mut generics = HashMap::new();
+        // function types don't have parameter names, so only arguments given in order can be checked
+        let (named, args): (Vec<_>, Vec<_>) = args.iter().partition(|arg| {
+            matches!(arg, Syntax::Operation(lhs, Operation::Equal(1), ..) if matches!(**lhs, Syntax::Ident(..)))
+        });
+        for arg in named {
+            if let Syntax::Operation(_, _, value, _) = arg {
+                self.infer(value, None);
+            }
+        }
+        for (idx, arg) in args.into_iter().enumerate() {
+            let param = params.get(idx);
+            let actual = self.infer(arg, param);
+            if let (Some(param), Some(actual)) = (param, actual) {
+                if !fits(&actual, param, &mut generics) {
+                    self.errors.push(
+                        DreamBerdError::new(
+                            ErrorKind::Type,
+                            format!(
+                                "argument {} of `{func}` should be `{param}`, but it's `{actual}`",
+                                idx + 1
+                            ),
+                        )
+                        .with_span(arg.span()),
+                    );
+                }
+            }
+        }
+        None
+    }
+}
+
+fn base(name: &str) -> Type {
+    Type::Named(name.into(), Vec::new())
+}
+
+fn base_of(typ: &Type) -> Option<BaseType> {
+    match typ {
+        Type::Named(name, _) => BaseType::from_name(name),
+        Type::Array(_) => None,
+    }
+}
+
+const fn is_numeric(base: BaseType) -> bool {
+    matches!(base, BaseType::Int | BaseType::UInt | BaseType::Number)
+}
+
+/// get the type of a number or keyword, as long as it hasn't been redefined
+fn literal_type(name: &str) -> Option<Type> {
+    match name {
+        "true" | "false" | "maybe" => return Some(base("Boolean")),
+        "undefined" => return Some(base("undefined")),
+        _ => {}
+    }
+    let num = name.parse::<f64>().ok()?;
+    Some(base(if num.fract() != 0.0 {
+        "Number"
+    } else if num < 0.0 {
+        "Int"
+    } else {
+        "UInt"
+    }))
+}
+
+/// a type name that isn't built in, like `T`, stands for whatever type it's used with; `_` can be a different type every time
+fn is_generic(typ: &Type) -> bool {
+    matches!(typ, Type::Named(name, args) if args.is_empty() && name.as_ref() != "Option" && BaseType::from_name(name).is_none())
+}
+
+/// check whether a value of type `actual` can be used where `expected` is needed, filling in `generics` as we go
+fn fits(actual: &Type, expected: &Type, generics: &mut HashMap<Rc<str>, Type>) -> bool {
+    if let (true, Type::Named(name, _)) = (is_generic(expected), expected) {
+        if name.as_ref() == "_" {
+            return true;
+        }
+        if let Some(bound) = generics.get(name).cloned() {
+            return fits(actual, &bound, generics);
+        }
+        generics.insert(name.clone(), actual.clone());
+        return true;
+    }
+    if is_generic(actual) {
+        // we don't know anything about it
+        return true;
+    }
+    match (actual, expected) {
+        (Type::Array(actual), Type::Array(expected)) => fits(actual, expected, generics),
+        (Type::Named(name, args), Type::Named(option, option_args))
+            if option.as_ref() == "Option" =>
+        {
+            if name.as_ref() == "Option" {
+                args.iter()
+                    .zip(option_args)
+                    .all(|(actual, expected)| fits(actual, expected, generics))
+            } else {
+                BaseType::from_name(name) == Some(BaseType::Undefined)
+                    || option_args
+                        .iter()
+                        .all(|expected| fits(actual, expected, generics))
+            }
+        }
+        // arrays are objects
+        (Type::Named(name, _), Type::Array(_)) => matches!(
+            BaseType::from_name(name),
+            Some(BaseType::Object | BaseType::Undefined) | None
+        ),
+        (Type::Array(_), Type::Named(name, _)) => {
+            matches!(BaseType::from_name(name), Some(BaseType::Object) | None)
+        }
+        (Type::Named(name, args), Type::Named(expected_name, expected_args)) => {
+            match (
+                BaseType::from_name(name),
+                BaseType::from_name(expected_name),
+            ) {
+                (Some(BaseType::Function), Some(BaseType::Function)) => {
+                    // a function has to accept everything that it might be called with
+                    args.iter()
+                        .zip(expected_args)
+                        .all(|(param, expected_param)| {
+                            let expected_param = resolve(expected_param, generics);
+                            fits(&expected_param, param, &mut HashMap::new())
+                        })
+                }
+                (Some(base), Some(expected_base)) => base.fits(expected_base),
+                // we don't know what other names mean
+                _ => true,
+            }
+        }
+    }
+}
+
+/// replace generics that we know the type of
+fn resolve(typ: &Type, generics: &HashMap<Rc<str>, Type>) -> Type {
+    match typ {
+        Type::Named(name, args) if args.is_empty() => {
+            generics.get(name).cloned().unwrap_or_else(|| typ.clone())
+        }
+        Type::Named(name, args) => Type::Named(
+            name.clone(),
+            args.iter().map(|arg| resolve(arg, generics)).collect(),
+        ),
+        Type::Array(inner) => Type::Array(Box::new(resolve(inner, generics))),
+    }
+}
@@ -0,0 +1,129 @@
+use std::{fmt::Display, rc::Rc};
+
+use super::Value;
+
+/// A type annotation, like `String`, `Fn<T[], i32>`, or `Option<T>`
+#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+pub enum Type {
+    /// a type name and its generic arguments
+    Named(Rc<str>, Vec<Self>),
+    /// `T[]`
+    Array(Box<Self>),
+}
+
+/// The kinds of value that built-in type names stand for
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
+pub enum BaseType {
+    Int,
+    UInt,
+    Number,
+    String,
+    Boolean,
+    Function,
+    Object,
+    Undefined,
+}
+
+impl BaseType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "Int" | "Integer" => Self::Int,
+            "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "UInt" => Self::UInt,
+            "f32" | "f64" | "Number" | "Float" => Self::Number,
+            "String" | "str" | "Str" => Self::String,
+            "bool" | "Bool" | "Boolean" => Self::Boolean,
+            "Fn" | "Function" => Self::Function,
+            "Object" => Self::Object,
+            "undefined" | "Undefined" => Self::Undefined,
+            _ => return None,
+        })
+    }
+
+    /// check whether values of this type can be used where `expected` is needed
+    pub fn fits(self, expected: Self) -> bool {
+        self == expected
+            || matches!(
+                (self, expected),
+                (Self::UInt, Self::Int | Self::Number)
+                    | (Self::Int, Self::Number)
+                    | (Self::Undefined, Self::Object)
+            )
+    }
+
+    pub fn accepts(self, value: &Value) -> bool {
+        match (self, value) {
+            (Self::Int, Value::Number(num)) => num.fract() == 0.0,
+            (Self::UInt, Value::Number(num)) => num.fract() == 0.0 && *num >= 0.0,
+            (Self::Number, Value::Number(_))
+            | (Self::String, Value::String(_))
+            | (Self::Boolean, Value::Boolean(_))
+            | (Self::Function, Value::Function(..) | Value::Keyword(_) | Value::Class(_))
+            | (Self::Object, Value::Object(_)) => true,
+            // objects with a `call` attribute can be called like functions
+            (Self::Function, Value::Object(obj)) => obj.contains_key(&Value::from("call")),
+            (Self::Undefined, _) => *value == Value::empty_object(),
+            _ => false,
+        }
+    }
+
+    /// the smallest and largest values of a sized integer type like `u8`
+    #[allow(clippy::cast_precision_loss)]
+    fn bounds(name: &str) -> Option<(f64, f64)> {
+        Some(match name {
+            "i8" => (f64::from(i8::MIN), f64::from(i8::MAX)),
+            "i16" => (f64::from(i16::MIN), f64::from(i16::MAX)),
+            "i32" => (f64::from(i32::MIN), f64::from(i32::MAX)),
+            "i64" | "isize" => (i64::MIN as f64, i64::MAX as f64),
+            "i128" => (i128::MIN as f64, i128::MAX as f64),
+            "u8" => (0.0, f64::from(u8::MAX)),
+            "u16" => (0.0, f64::from(u16::MAX)),
+            "u32" => (0.0, f64::from(u32::MAX)),
+            "u64" | "usize" => (0.0, u64::MAX as f64),
+            "u128" => (0.0, u128::MAX as f64),
+            _ => return None,
+        })
+    }
+}
+
+impl Type {
+    /// Check whether `value` fits this type. Generics and names we don't know about accept anything.
+    pub fn accepts(&self, value: &Value) -> bool {
+        match self {
+            Self::Array(inner) => match value {
+                Value::Array(items) => items
+                    .iter()
+                    .all(|item| item.with_ref(|item| inner.accepts(item))),
+                // objects can stand in for arrays
+                Value::Object(_) => true,
+                _ => false,
+            },
+            Self::Named(name, args) if name.as_ref() == "Option" => {
+                BaseType::Undefined.accepts(value) || args.iter().all(|arg| arg.accepts(value))
+            }
+            Self::Named(name, _) => BaseType::from_name(name)
+                .is_none_or(|base| base.accepts(value))
+                && BaseType::bounds(name).is_none_or(
+                    |(min, max)| matches!(value, Value::Number(num) if (min..=max).contains(num)),
+                ),
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Named(name, args) => {
+                write!(f, "{name}")?;
+                if let Some((first, rest)) = args.split_first() {
+                    write!(f, "<{first}")?;
+                    for arg in rest {
+                        write!(f, ", {arg}")?;
+                    }
+                    write!(f, ">")?;
+                }
+                Ok(())
+            }
+            Self::Array(inner) => write!(f, "{inner}[]"),
+        }
+    }
+}
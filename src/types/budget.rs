@@ -0,0 +1,62 @@
+use std::{
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+use super::{Config, DreamBerdError, ErrorKind, SResult};
+
+/// How much longer code is allowed to run, from `--fuel` and `--timeout`. Every statement in a block and every
+/// call is one step, so anything that goes on forever uses up steps.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Budget {
+    fuel: Option<u64>,
+    timeout: Option<Duration>,
+    /// the steps that are left, if there's a limit
+    fuel_left: Cell<Option<u64>>,
+    deadline: Cell<Option<Instant>>,
+}
+
+impl Budget {
+    pub fn new(config: &Config) -> Self {
+        let budget = Self {
+            fuel: config.fuel,
+            timeout: config.timeout,
+            fuel_left: Cell::new(None),
+            deadline: Cell::new(None),
+        };
+        budget.refill();
+        budget
+    }
+
+    /// Start over with all of the fuel and time, like before each line in the REPL. Running out doesn't change
+    /// any variables, so the same state can keep going after this.
+    pub fn refill(&self) {
+        self.fuel_left.set(self.fuel);
+        self.deadline.set(
+            self.timeout
+                .and_then(|timeout| Instant::now().checked_add(timeout)),
+        );
+    }
+
+    /// use up one step, or fail if there's no fuel or time left
+    pub fn step(&self) -> SResult<()> {
+        if let (Some(fuel), Some(left)) = (self.fuel, self.fuel_left.get()) {
+            let Some(left) = left.checked_sub(1) else {
+                return Err(DreamBerdError::new(
+                    ErrorKind::OutOfFuel,
+                    format!("Ran out of fuel after {fuel} steps"),
+                ));
+            };
+            self.fuel_left.set(Some(left));
+        }
+        if let (Some(timeout), Some(deadline)) = (self.timeout, self.deadline.get()) {
+            if Instant::now() >= deadline {
+                return Err(DreamBerdError::new(
+                    ErrorKind::Timeout,
+                    format!("Ran out of time after {timeout:?}"),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
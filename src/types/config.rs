@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+/// Settings that change how programs run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// check values against their type annotations when they're bound to a name
+    pub strict_types: bool,
+    /// functions run inside of the scope they're called from, instead of the one they were made in
+    pub dynamic_scope: bool,
+    /// what runs the code
+    pub engine: Engine,
+    /// the most calls to user-defined functions that can be running at once. Calls at the end of a function
+    /// don't count, because they take the place of the function that made them.
+    pub max_depth: usize,
+    /// the most steps that code can take, where every statement in a block and every call is a step
+    pub fuel: Option<u64>,
+    /// how long code can run for
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            strict_types: false,
+            dynamic_scope: false,
+            engine: Engine::default(),
+            max_depth: 1000,
+            fuel: None,
+            timeout: None,
+        }
+    }
+}
+
+/// The ways that code can be run. They only differ in how fast they are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Engine {
+    /// walk over the syntax tree
+    #[default]
+    Tree,
+    /// compile to bytecode, and run that on a stack machine
+    Vm,
+}
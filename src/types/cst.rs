@@ -0,0 +1,50 @@
+use std::{fmt::Display, rc::Rc};
+
+use super::{Span, Token};
+
+/// A node of the concrete syntax tree. Unlike [`Syntax`](super::Syntax), it keeps every character of the source,
+/// so displaying it gives back exactly the code it was parsed from.
+#[derive(PartialEq, Debug, Clone, Eq)]
+pub enum Cst {
+    /// a token and the exact text it was lexed from
+    Token(Token, Rc<str>, Span),
+    /// a `//` or `/* */` comment, including the slashes
+    Comment(Rc<str>, Span),
+    /// a statement, including the `!` or `?` at the end of it if there is one
+    Statement(Vec<Self>, Span),
+    /// a pair of braces, parentheses, or square brackets, along with everything between them
+    Group(Vec<Self>, Span),
+    /// a whole source file
+    File(Vec<Self>, Span),
+}
+
+impl Cst {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Token(.., span)
+            | Self::Comment(.., span)
+            | Self::Statement(.., span)
+            | Self::Group(.., span)
+            | Self::File(.., span) => *span,
+        }
+    }
+
+    /// whitespace and comments don't change what the code means
+    pub const fn is_trivia(&self) -> bool {
+        matches!(self, Self::Comment(..) | Self::Token(Token::Space(_), ..))
+    }
+}
+
+impl Display for Cst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Token(_, text, _) | Self::Comment(text, _) => write!(f, "{text}"),
+            Self::Statement(children, _) | Self::Group(children, _) | Self::File(children, _) => {
+                for child in children {
+                    write!(f, "{child}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    error::Error,
+    fmt::{Display, Write},
+    rc::Rc,
+};
+
+use super::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// the source code couldn't be split into tokens
+    Lex,
+    /// the tokens don't make a valid program
+    Parse,
+    /// something went wrong while running the program
+    Runtime,
+    /// tried to change a value that can't be changed
+    Assignment,
+    /// a function or keyword got the wrong number of arguments
+    Arity,
+    /// a value doesn't match its type annotation
+    Type,
+    /// the code took more steps than `--fuel` allows
+    OutOfFuel,
+    /// the code ran for longer than `--timeout` allows
+    Timeout,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lex => write!(f, "lex"),
+            Self::Parse => write!(f, "parse"),
+            Self::Runtime => write!(f, "runtime"),
+            Self::Assignment => write!(f, "assignment"),
+            Self::Arity => write!(f, "arity"),
+            Self::Type => write!(f, "type"),
+            Self::OutOfFuel => write!(f, "fuel"),
+            Self::Timeout => write!(f, "timeout"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DreamBerdError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<String>,
+    /// the code ran out before it was finished, so more code might fix it
+    pub incomplete: bool,
+    /// the functions that were running when the error happened, with the one that was called last first
+    pub stack: Vec<Frame>,
+}
+
+impl DreamBerdError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            span: None,
+            notes: Vec::new(),
+            incomplete: false,
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn lex(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Lex, message)
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Parse, message)
+    }
+
+    pub fn runtime(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Runtime, message)
+    }
+
+    pub fn assignment(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Assignment, message)
+    }
+
+    pub fn arity(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Arity, message)
+    }
+
+    #[must_use]
+    pub const fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// set the span if the error doesn't already know where it happened
+    #[must_use]
+    pub fn or_span(mut self, span: Span) -> Self {
+        self.span.get_or_insert(span);
+        self
+    }
+
+    /// mark that the error happened because the code isn't finished yet
+    #[must_use]
+    pub const fn incomplete(mut self) -> Self {
+        self.incomplete = true;
+        self
+    }
+
+    #[must_use]
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// set the stack trace if the error doesn't already have one from further in
+    #[must_use]
+    pub fn or_stack(mut self, stack: &[Frame]) -> Self {
+        if self.stack.is_empty() {
+            self.stack = stack.iter().rev().cloned().collect();
+        }
+        self
+    }
+
+    /// Render the error like rustc does, with the offending line of `source` and a caret underline
+    pub fn render(&self, source: &str, path: &str) -> String {
+        let mut out = String::new();
+        // writing to a `String` can't fail
+        let _ = self.write_rendered(&mut out, source, path);
+        out
+    }
+
+    fn write_rendered(&self, out: &mut impl Write, source: &str, path: &str) -> std::fmt::Result {
+        writeln!(out, "error[{}]: {}", self.kind, self.message)?;
+        let Some(span) = self.span else {
+            for note in &self.notes {
+                writeln!(out, "  = note: {note}")?;
+            }
+            return self.write_stack(out, " ", path);
+        };
+        let line_no = span.line.to_string();
+        let gutter = " ".repeat(line_no.len());
+        writeln!(out, "{gutter}--> {path}:{span}")?;
+        if let Some(line) = source.lines().nth(span.line.saturating_sub(1)) {
+            let line = line.trim_end_matches('\r');
+            // the span is measured in bytes, but the underline is measured in characters
+            let line_start = source[..span.start.min(source.len())]
+                .rfind('\n')
+                .map_or(0, |idx| idx + 1);
+            let underline_end = (span.end.max(span.start + 1))
+                .min(line_start + line.len())
+                .max(span.start);
+            let width = source
+                .get(span.start..underline_end)
+                .map_or(1, |text| text.chars().count().max(1));
+            writeln!(out, "{gutter} |")?;
+            writeln!(out, "{line_no} | {line}")?;
+            writeln!(
+                out,
+                "{gutter} | {}{}",
+                " ".repeat(span.col.saturating_sub(1)),
+                "^".repeat(width)
+            )?;
+        }
+        for note in &self.notes {
+            writeln!(out, "{gutter} = note: {note}")?;
+        }
+        self.write_stack(out, &gutter, path)
+    }
+
+    fn write_stack(&self, out: &mut impl Write, gutter: &str, path: &str) -> std::fmt::Result {
+        if self.stack.is_empty() {
+            return Ok(());
+        }
+        writeln!(out, "{gutter} = stack trace:")?;
+        for (idx, frame) in self.stack.iter().enumerate().take(MAX_SHOWN_FRAMES) {
+            writeln!(
+                out,
+                "{gutter}     {idx}: {} at {path}:{}",
+                frame.name(),
+                frame.call_site
+            )?;
+        }
+        if let Some(hidden) = self.stack.len().checked_sub(MAX_SHOWN_FRAMES) {
+            if hidden > 0 {
+                writeln!(out, "{gutter}     ...and {hidden} more")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// the most frames of a stack trace that get printed, since runaway recursion can make thousands of them
+const MAX_SHOWN_FRAMES: usize = 20;
+
+/// A call to a user-defined function that hasn't finished yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// the name that the function was declared with, if it has one
+    pub name: Option<Rc<str>>,
+    /// where the function was called
+    pub call_site: Span,
+}
+
+impl Frame {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("<anonymous>")
+    }
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.name(), self.call_site)
+    }
+}
+
+impl Display for DreamBerdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(span) = self.span {
+            write!(f, "{span}: ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for DreamBerdError {}
+
+impl From<String> for DreamBerdError {
+    fn from(value: String) -> Self {
+        Self::runtime(value)
+    }
+}
+
+impl From<&str> for DreamBerdError {
+    fn from(value: &str) -> Self {
+        Self::runtime(value)
+    }
+}
@@ -1,22 +1,35 @@
 pub use prelude::*;
 
+mod annotation;
+mod budget;
+mod config;
+mod cst;
+mod error;
 mod pointer;
+mod span;
 mod state;
 mod syntax;
 mod token;
 mod value;
+pub mod visit;
 
 pub mod prelude {
     use std::cell::RefCell;
     use std::rc::Rc;
 
+    pub use super::annotation::{BaseType, Type};
+    pub use super::budget::Budget;
+    pub use super::config::{Config, Engine};
+    pub use super::cst::Cst;
+    pub use super::error::{DreamBerdError, ErrorKind, Frame};
     pub use super::pointer::{MutValue, Pointer};
+    pub use super::span::Span;
     pub use super::state::State;
-    pub use super::syntax::{Lifetime, Operation, Syntax, UnaryOperation, VarType};
+    pub use super::syntax::{Lifetime, Operation, Param, Syntax, UnaryOperation, VarType};
     pub use super::token::{StringSegment, Token};
-    pub use super::value::{Boolean, Keyword, Value};
+    pub use super::value::{Boolean, Closure, Keyword, Value};
 
-    pub type SResult<T> = Result<T, String>;
+    pub type SResult<T> = Result<T, DreamBerdError>;
     pub type RcMut<T> = Rc<RefCell<T>>;
 
     pub fn rc_mut_new<T>(content: T) -> RcMut<T> {
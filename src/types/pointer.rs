@@ -23,7 +23,7 @@ pub enum Pointer {
 pub struct MutValue {
     pub value: Value,
     pub previous: Option<Value>,
-    pub event_listeners: Vec<(Syntax, RcMut<State>)>,
+    pub event_listeners: Vec<(Rc<Syntax>, RcMut<State>)>,
     next_handles: Vec<Pointer>,
 }
 
@@ -32,7 +32,7 @@ impl MutValue {
         self.previous = Some(core::mem::replace(&mut self.value, value));
     }
 
-    pub fn add_event_listener(&mut self, listener: Syntax, state: RcMut<State>) {
+    pub fn add_event_listener(&mut self, listener: Rc<Syntax>, state: RcMut<State>) {
         self.event_listeners.push((listener, state));
     }
 
@@ -101,6 +101,33 @@ impl Pointer {
         }
     }
 
+    /// whether this is the only pointer to a function that was made in `scope`
+    pub fn is_only_function_made_in(&self, scope: &RcMut<State>) -> bool {
+        let made_in = |val: &Value| {
+            matches!(val, Value::Function(.., closure)
+                if closure.made_in().is_some_and(|made_in| Rc::ptr_eq(made_in, scope)))
+        };
+        match self {
+            Self::ConstConst(val) => Rc::strong_count(val) == 1 && made_in(val),
+            Self::ConstVar(var) => {
+                Rc::strong_count(var) == 1 && var.try_borrow().is_ok_and(|var| made_in(&var.value))
+            }
+            Self::VarConst(val) => {
+                Rc::strong_count(val) == 1
+                    && val
+                        .try_borrow()
+                        .is_ok_and(|val| Rc::strong_count(&val) == 1 && made_in(&val))
+            }
+            Self::VarVar(var) => {
+                Rc::strong_count(var) == 1
+                    && var.try_borrow().is_ok_and(|var| {
+                        Rc::strong_count(&var) == 1
+                            && var.try_borrow().is_ok_and(|var| made_in(&var.value))
+                    })
+            }
+        }
+    }
+
     /// Convert this pointer to a different type. Performs a shallow clone if switching between inner `const` and `var`
     pub fn convert(&self, vt: VarType) -> Self {
         match vt {
@@ -149,7 +176,12 @@ impl Pointer {
     }
 
     /// Apply the dot operator; object indexing. Otherwise, it returns `undefined`
-    #[allow(clippy::option_if_let_else, clippy::single_match_else)]
+    #[allow(
+        clippy::option_if_let_else,
+        clippy::single_match_else,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
     pub fn dot(&self, rhs: &Value) -> Self {
         let allow_modify = matches!(self, Self::ConstVar(_) | Self::VarVar(_));
         let lhs = self.clone_inner();
@@ -166,6 +198,7 @@ impl Pointer {
                     val
                 }
             },
+            (Value::Array(_), Value::Number(_)) => self.index(rhs),
             (Value::String(str), Value::Number(num)) => match *num as i32 {
                 i @ ..=-2 => str.chars().nth(str.len() + ((-i + 1) as usize)),
                 i @ -1.. => str.chars().nth((i + 1) as usize),
@@ -178,11 +211,42 @@ impl Pointer {
         }
     }
 
+    /// Apply the index operator. Arrays start at -1, and indexes that aren't in the array give `undefined`.
+    /// Anything else is indexed like the dot operator.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn index(&self, rhs: &Value) -> Self {
+        match (self.clone_inner(), rhs) {
+            (Value::Array(items), &Value::Number(idx)) => {
+                if idx.fract() != 0.0 || idx < -1.0 {
+                    return Self::from(Value::empty_object());
+                }
+                items
+                    .get((idx + 1.0) as usize)
+                    .cloned()
+                    .unwrap_or_else(|| Self::from(Value::empty_object()))
+            }
+            _ => self.dot(rhs),
+        }
+    }
+
+    /// Replace the value being pointed to, if it's `var`; otherwise, nothing happens, just like with `+=`
+    pub fn set_inner(&self, value: Value) {
+        match self {
+            Self::ConstVar(val) => val.borrow_mut().assign(value),
+            Self::VarVar(val) => val.borrow().borrow_mut().assign(value),
+            Self::ConstConst(_) | Self::VarConst(_) => {}
+        }
+    }
+
     /// Try to replace the current value with given value. Returns `Err` if `self` is ptr-const. Doesn't clone if it's not necessary.
     pub fn assign(&self, rhs: &Self) -> SResult<()> {
         match self {
-            Self::ConstConst(_) => Err(format!("Can't assign to a `const const` {self:?}")),
-            Self::ConstVar(_) => Err(format!("Can't assign to a `const var` {self:?}")),
+            Self::ConstConst(_) => Err(DreamBerdError::assignment(format!(
+                "Can't assign to a `const const` {self:?}"
+            ))),
+            Self::ConstVar(_) => Err(DreamBerdError::assignment(format!(
+                "Can't assign to a `const var` {self:?}"
+            ))),
             Self::VarConst(ptr) => {
                 ptr.replace(rhs.make_const());
                 Ok(())
@@ -274,7 +338,7 @@ impl AddAssign for Pointer {
                 val.borrow().borrow_mut().assign(output);
             }
             _ => {}
-        };
+        }
     }
 }
 
@@ -297,7 +361,7 @@ impl SubAssign for Pointer {
                 val.borrow().borrow_mut().assign(output);
             }
             _ => {}
-        };
+        }
     }
 }
 
@@ -320,7 +384,7 @@ impl MulAssign for Pointer {
                 val.borrow().borrow_mut().assign(output);
             }
             _ => {}
-        };
+        }
     }
 }
 
@@ -343,7 +407,7 @@ impl DivAssign for Pointer {
                 val.borrow().borrow_mut().assign(output);
             }
             _ => {}
-        };
+        }
     }
 }
 
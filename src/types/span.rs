@@ -0,0 +1,57 @@
+use std::{fmt::Display, hash::Hash};
+
+/// A location in the source code; a range of byte offsets, along with the line and column where it starts.
+///
+/// Spans always compare equal to each other, so they don't affect the equality or hash of the tokens and syntax nodes that carry them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    pub const fn new(start: usize, end: usize, line: usize, col: usize) -> Self {
+        Self {
+            start,
+            end,
+            line,
+            col,
+        }
+    }
+
+    /// Get the smallest span that covers both `self` and `other`
+    #[must_use]
+    pub fn to(self, other: Self) -> Self {
+        let first = if other.start < self.start {
+            other
+        } else {
+            self
+        };
+        Self {
+            start: first.start,
+            end: self.end.max(other.end),
+            line: first.line,
+            col: first.col,
+        }
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Span {}
+
+impl Hash for Span {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
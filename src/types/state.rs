@@ -1,7 +1,7 @@
 use std::{
     cell::RefCell,
-    collections::{BTreeMap, HashMap},
-    rc::Rc,
+    collections::{BTreeMap, HashMap, HashSet},
+    rc::{Rc, Weak},
 };
 
 use lazy_regex::regex;
@@ -10,11 +10,73 @@ use crate::types::prelude::*;
 
 use core::f64::consts as f64;
 
+/// the variables that have been exported to each file, by the name of the file
+type Exports = HashMap<Rc<str>, Vec<(Rc<str>, Pointer)>>;
+
+/// how many scopes are kept track of before they're checked for the first time
+const MIN_SCOPES: usize = 64;
+
+/// The scopes that functions have been made in.
+///
+/// A function keeps the scope it was made in alive, and it's usually kept in that same scope, so once
+/// nothing else can get to either of them, they'd keep each other alive forever. Every so often, these are
+/// checked for that, and the ones it's happened to are emptied so that they can be dropped.
+#[derive(Debug, Default)]
+pub struct Scopes {
+    scopes: Vec<Weak<RefCell<State>>>,
+    /// how many there can be before they're checked again
+    limit: usize,
+}
+
+impl Scopes {
+    /// Empty every scope that's only being kept alive by functions that were made in it, and that are only
+    /// kept in it. Nothing can ever run those functions again, or see that they're gone.
+    pub fn collect(&mut self) {
+        let mut seen = HashSet::new();
+        let mut kept = Vec::new();
+        for weak in std::mem::take(&mut self.scopes) {
+            let Some(scope) = weak.upgrade() else {
+                continue;
+            };
+            if !seen.insert(Rc::as_ptr(&scope)) {
+                continue;
+            }
+            if State::only_kept_by_its_functions(&scope) {
+                // the functions are dropped after the scope isn't borrowed anymore, since that drops the scope
+                let current = std::mem::take(&mut scope.borrow_mut().current);
+                drop(current);
+            } else {
+                kept.push(weak);
+            }
+        }
+        self.limit = kept.len().saturating_mul(2).max(MIN_SCOPES);
+        self.scopes = kept;
+    }
+}
+
+// which scopes are being kept track of doesn't change what a state is
+impl PartialEq for Scopes {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Scopes {}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct State {
     current: HashMap<Rc<str>, (Pointer, Lifetime)>,
-    parent: Option<RcMut<State>>,
+    parent: Option<RcMut<Self>>,
     pub undefined: Pointer,
+    pub config: Rc<Config>,
+    /// the variables that have been exported to each file, which it gets when it starts
+    exports: RcMut<Exports>,
+    /// the user-defined functions that are running, with the one that was called last at the end
+    pub calls: RcMut<Vec<Frame>>,
+    /// how much longer the code can run for
+    pub budget: Rc<Budget>,
+    /// the scopes that functions have been made in
+    scopes: RcMut<Scopes>,
 }
 
 macro_rules! kw {
@@ -26,6 +88,12 @@ macro_rules! kw {
     };
 }
 
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl State {
     pub fn new() -> Self {
         let mut current = HashMap::new();
@@ -41,10 +109,15 @@ impl State {
         kw!(current "forget" => Keyword::Forget);
         kw!(current "if" => Keyword::If);
         kw!(current "infinity" => Value::Number(f64::INFINITY));
+        kw!(current "len" => Keyword::Len);
         kw!(current "maybe" => Boolean::Maybe);
         kw!(current "next" => Keyword::Next);
         kw!(current "new" => Keyword::New);
+        kw!(current "pop" => Keyword::Pop);
         kw!(current "previous" => Keyword::Previous);
+        kw!(current "push" => Keyword::Push);
+        kw!(current "return" => Keyword::Return);
+        kw!(current "stack" => Keyword::Stack);
         kw!(current "true" => true);
         kw!(current "var" => Keyword::Var);
         kw!(current "when" => Keyword::When);
@@ -56,18 +129,70 @@ impl State {
             current,
             parent: None,
             undefined,
+            config: Rc::default(),
+            exports: RcMut::default(),
+            calls: RcMut::default(),
+            budget: Rc::new(Budget::new(&Config::default())),
+            scopes: RcMut::default(),
+        }
+    }
+
+    pub fn with_config(config: Config) -> Self {
+        Self {
+            budget: Rc::new(Budget::new(&config)),
+            config: Rc::new(config),
+            ..Self::new()
         }
     }
 
     pub fn from_parent(parent: Rc<RefCell<Self>>) -> Self {
         let undefined = parent.borrow().undefined.clone();
+        let config = parent.borrow().config.clone();
+        let exports = parent.borrow().exports.clone();
+        let calls = parent.borrow().calls.clone();
+        let budget = parent.borrow().budget.clone();
+        let scopes = parent.borrow().scopes.clone();
         Self {
             current: HashMap::new(),
             undefined,
+            config,
+            exports,
+            calls,
+            budget,
+            scopes,
             parent: Some(parent),
         }
     }
 
+    /// Make the global scope for another file in the same source, with everything that's been exported to it.
+    /// Files only share their config, their exports, the call stack, and the budget.
+    pub fn for_file(&self, name: Option<&str>) -> Self {
+        let mut state = Self {
+            config: self.config.clone(),
+            exports: self.exports.clone(),
+            calls: self.calls.clone(),
+            budget: self.budget.clone(),
+            scopes: self.scopes.clone(),
+            ..Self::new()
+        };
+        let imports = name
+            .and_then(|name| self.exports.borrow_mut().remove(name))
+            .unwrap_or_default();
+        for (key, value) in imports {
+            state.insert(key, value, Lifetime::Default);
+        }
+        state
+    }
+
+    /// give the file named `file` a copy of a variable, for when it starts
+    pub fn export(&self, file: Rc<str>, key: Rc<str>, value: Pointer) {
+        self.exports
+            .borrow_mut()
+            .entry(file)
+            .or_default()
+            .push((key, value));
+    }
+
     pub fn get(&mut self, key: Rc<str>) -> Pointer {
         // println!("{:?}: {key}", self.current);
         // if there's a value here, get it
@@ -95,6 +220,66 @@ impl State {
         }
     }
 
+    /// Keep track of `this` now that a function has been made in it, so that the two can be dropped once
+    /// nothing else uses them. Sometimes this checks the scopes it's kept track of, too.
+    pub fn made_function(this: &RcMut<Self>) {
+        let scopes = this.borrow().scopes.clone();
+        let mut scopes = scopes.borrow_mut();
+        if scopes
+            .scopes
+            .last()
+            .is_some_and(|last| last.as_ptr() == Rc::as_ptr(this))
+        {
+            return;
+        }
+        scopes.scopes.push(Rc::downgrade(this));
+        if scopes.scopes.len() >= scopes.limit.max(MIN_SCOPES) {
+            scopes.collect();
+        }
+    }
+
+    /// the scopes that functions have been made in, which are shared by every state of a program
+    pub fn scopes(&self) -> RcMut<Scopes> {
+        self.scopes.clone()
+    }
+
+    /// Whether the only things keeping `this` alive are functions that were made in it, and are only kept in
+    /// its variables, apart from the one that's being looked at. A scope that's being used is never like that.
+    fn only_kept_by_its_functions(this: &RcMut<Self>) -> bool {
+        let Ok(state) = this.try_borrow() else {
+            return false;
+        };
+        let functions = state
+            .current
+            .values()
+            .filter(|(value, _)| value.is_only_function_made_in(this))
+            .count();
+        Rc::strong_count(this) == functions + 1
+    }
+
+    /// Put every variable that can be seen from `this` into one scope inside of `base`, which has to be one of
+    /// the scopes that `this` is inside of. Variables further in hide the ones with the same name further out.
+    pub fn flatten(this: &RcMut<Self>, base: &RcMut<Self>) -> Self {
+        let mut flat = Self::from_parent(base.clone());
+        let mut scope = this.clone();
+        while !Rc::ptr_eq(&scope, base) {
+            let parent = {
+                let borrowed = scope.borrow();
+                for (key, value) in &borrowed.current {
+                    flat.current
+                        .entry(key.clone())
+                        .or_insert_with(|| value.clone());
+                }
+                borrowed.parent.clone()
+            };
+            match parent {
+                Some(parent) => scope = parent,
+                None => break,
+            }
+        }
+        flat
+    }
+
     pub fn insert(&mut self, key: Rc<str>, value: Pointer, lifetime: Lifetime) {
         self.current.insert(key, (value, lifetime));
     }
@@ -1,68 +1,222 @@
 use std::{fmt::Display, hash::Hash, rc::Rc};
 
-use super::{StringSegment, Token};
+use super::{Span, StringSegment, Token, Type};
 
+/// A node of the syntax tree. The last field of every variant is the span of source code it was parsed from.
 #[derive(PartialEq, Debug, Clone, Eq, Hash)]
 pub enum Syntax {
-    Declare(VarType, Rc<str>, Lifetime, Box<Syntax>),
-    Function(Vec<Rc<str>>, Box<Syntax>),
-    Operation(Box<Syntax>, Operation, Box<Syntax>),
-    UnaryOperation(UnaryOperation, Box<Syntax>),
-    Ident(Rc<str>),
-    String(Vec<StringSegment>),
-    Block(Vec<Syntax>),
-    Statement(bool, Box<Syntax>, u8),
+    Declare(VarType, Rc<str>, Lifetime, Option<Type>, Box<Self>, Span),
+    /// declare variables from the fields of an object, like `const const {name, health: hp} = player!`.
+    /// Each field comes with the name of the variable it goes in.
+    Destructure(VarType, Vec<(Rc<str>, Rc<str>)>, Lifetime, Box<Self>, Span),
+    /// a function literal; the body is shared with every function value that's made from it
+    Function(Vec<Param>, Rc<Self>, Span),
+    Operation(Box<Self>, Operation, Box<Self>, Span),
+    UnaryOperation(UnaryOperation, Box<Self>, Span),
+    Ident(Rc<str>, Span),
+    String(Vec<StringSegment<Self>>, Span),
+    Block(Vec<Self>, Span),
+    /// an array literal, like `[1, 2, 3]`
+    Array(Vec<Self>, Span),
+    Statement(bool, Box<Self>, u8, Span),
+    /// an expression with a type annotation, like `value: i32`
+    Annotated(Box<Self>, Type, Span),
+    /// one of the files in a source that's split up with `=====` lines, with its name if it has one.
+    /// Each file runs in its own global scope.
+    File(Option<Rc<str>>, Box<Self>, Span),
+    /// `export name to "file"!`, which gives a file that comes later a copy of the variable
+    Export(Rc<str>, Box<Self>, Span),
+    /// a statement that couldn't be parsed; these only come from [`parse_recovering`](crate::parser::parse_recovering)
+    Error(Span),
+}
+
+impl Syntax {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Declare(.., span)
+            | Self::Destructure(.., span)
+            | Self::Function(.., span)
+            | Self::Operation(.., span)
+            | Self::UnaryOperation(.., span)
+            | Self::Ident(.., span)
+            | Self::String(.., span)
+            | Self::Block(.., span)
+            | Self::Array(.., span)
+            | Self::Statement(.., span)
+            | Self::Annotated(.., span)
+            | Self::File(.., span)
+            | Self::Export(.., span)
+            | Self::Error(span) => *span,
+        }
+    }
 }
 
 impl Display for Syntax {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Statement(is_debug, content, count) => {
+            Self::Statement(is_debug, content, count, _) => {
                 write!(
                     f,
                     "{content}{}",
                     if *is_debug { "?" } else { "!" }.repeat(*count as usize)
                 )
             }
-            Self::Block(statements) => {
+            Self::Block(statements, _) => {
                 write!(f, "{{")?;
                 for statement in statements {
                     write!(f, "{statement} ")?;
                 }
                 write!(f, "}}")
             }
-            Self::String(segments) => {
+            Self::Array(items, _) => {
+                write!(f, "[")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Self::String(segments, _) => {
                 write!(f, "\"")?;
                 for segment in segments {
                     write!(f, "{segment}")?;
                 }
                 write!(f, "\"")
             }
-            Self::Ident(ident) => write!(f, "{ident}"),
-            Self::Declare(var_type, name, lifetime, value) => {
+            Self::Ident(ident, _) => write!(f, "{ident}"),
+            Self::Error(_) => write!(f, "<error>"),
+            Self::Declare(var_type, name, lifetime, Some(annotation), value, _) => {
+                write!(f, "{var_type} {name}{lifetime}: {annotation} = {value}")
+            }
+            Self::Declare(var_type, name, lifetime, None, value, _) => {
                 write!(f, "{var_type} {name}{lifetime} = {value}")
             }
-            Self::Operation(lhs, op, rhs) => {
+            Self::Destructure(var_type, fields, lifetime, value, _) => {
+                write!(f, "{var_type} {{")?;
+                for (idx, (field, name)) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    if field == name {
+                        write!(f, "{field}")?;
+                    } else {
+                        write!(f, "{field}: {name}")?;
+                    }
+                }
+                write!(f, "}}{lifetime} = {value}")
+            }
+            Self::Annotated(inner, annotation, _) => write!(f, "{inner}: {annotation}"),
+            Self::File(Some(name), body, _) => write!(f, "===== {name} =====\n{body}"),
+            Self::File(None, body, _) => write!(f, "=====\n{body}"),
+            Self::Export(name, target, _) => write!(f, "export {name} to {target}"),
+            Self::Operation(lhs, Operation::Index, rhs, _) => write!(f, "{lhs}[{rhs}]"),
+            Self::Operation(lhs, op, rhs, _) => {
                 write!(f, "({lhs}{op}{rhs})")
             }
-            Self::UnaryOperation(UnaryOperation::Call(args), operand) => {
+            Self::UnaryOperation(UnaryOperation::Call(args), operand, _) => {
                 write!(f, "{operand}({args:?})")
             }
-            Self::UnaryOperation(UnaryOperation::Decrement, operand) => {
+            Self::UnaryOperation(UnaryOperation::Decrement, operand, _) => {
                 write!(f, "{operand}--")
             }
-            Self::UnaryOperation(UnaryOperation::Increment, operand) => {
+            Self::UnaryOperation(UnaryOperation::Increment, operand, _) => {
                 write!(f, "{operand}++")
             }
-            Self::Function(args, body) => {
-                write!(f, "{args:?} -> {body}")
+            Self::Function(args, body, _) => {
+                write!(f, "({}) -> {body}", Param::list(args))
             }
-            Self::UnaryOperation(UnaryOperation::Negate, inner) => write!(f, ";{inner}"),
+            Self::UnaryOperation(UnaryOperation::Negate, inner, _) => write!(f, ";{inner}"),
+            Self::UnaryOperation(UnaryOperation::Rest, inner, _) => write!(f, "...{inner}"),
             // other => write!(f, "{other:?}"),
         }
     }
 }
 
+#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+pub struct Param {
+    pub name: Rc<str>,
+    pub annotation: Option<Type>,
+    /// the value to use if the argument is left out, which is evaluated every time the function is called
+    pub default: Option<Rc<Syntax>>,
+    /// this is `...rest`, so it gets every argument that's left over as an array
+    pub rest: bool,
+}
+
+impl Param {
+    pub const fn new(name: Rc<str>) -> Self {
+        Self {
+            name,
+            annotation: None,
+            default: None,
+            rest: false,
+        }
+    }
+
+    /// get a parameter from an identifier that might have a type annotation and a default value, like `b: Int = 2`
+    pub fn from_syntax(syn: &Syntax) -> Option<Self> {
+        match syn {
+            Syntax::UnaryOperation(UnaryOperation::Rest, param, _) => {
+                let param = Self::from_syntax(param)
+                    .filter(|param| param.default.is_none() && !param.rest)?;
+                Some(Self {
+                    rest: true,
+                    ..param
+                })
+            }
+            Syntax::Operation(param, Operation::Equal(1), default, _) => {
+                let param = Self::from_syntax(param)
+                    .filter(|param| param.default.is_none() && !param.rest)?;
+                Some(Self {
+                    default: Some(Rc::new((**default).clone())),
+                    ..param
+                })
+            }
+            Syntax::Ident(name, _) => Some(Self::new(name.clone())),
+            Syntax::Annotated(inner, annotation, _) => match &**inner {
+                Syntax::Ident(name, _) => Some(Self {
+                    annotation: Some(annotation.clone()),
+                    ..Self::new(name.clone())
+                }),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// find a `...rest` parameter that isn't at the end, since nothing would be left over for the ones after it
+    pub fn misplaced_rest(params: &[Self]) -> Option<&Self> {
+        let (_, init) = params.split_last()?;
+        init.iter().find(|param| param.rest)
+    }
+
+    /// show a parameter list like `a, b: i32`
+    pub fn list(params: &[Self]) -> String {
+        params
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl Display for Param {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.rest {
+            write!(f, "...")?;
+        }
+        write!(f, "{}", self.name)?;
+        if let Some(annotation) = &self.annotation {
+            write!(f, ": {annotation}")?;
+        }
+        if let Some(default) = &self.default {
+            write!(f, " = {default}")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
 pub enum VarType {
     ConstConst,
@@ -88,7 +242,10 @@ pub enum UnaryOperation {
     Increment,
     Decrement,
     Negate,
-    Call(Vec<Syntax>),
+    /// the arguments are shared, so that they can be kept until the function decides whether to run them
+    Call(Rc<[Syntax]>),
+    /// `...rest`, which collects the rest of the arguments of a function into an array
+    Rest,
 }
 
 #[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
@@ -104,7 +261,17 @@ pub enum Operation {
     DivEq,
     Mod,
     ModEq,
+    /// `^`, raising to a power
+    Pow,
+    PowEq,
+    /// `//`, dividing and rounding down
+    FloorDiv,
+    FloorDivEq,
+    /// `in`, checking whether an object has a key, a string has a substring, or an array has an element
+    In,
     Dot,
+    /// `array[index]`
+    Index,
     And,
     Or,
     Arrow,
@@ -128,7 +295,13 @@ impl Display for Operation {
             Self::DivEq => write!(f, "/="),
             Self::Mod => write!(f, "%"),
             Self::ModEq => write!(f, "%="),
+            Self::Pow => write!(f, "^"),
+            Self::PowEq => write!(f, "^="),
+            Self::FloorDiv => write!(f, "//"),
+            Self::FloorDivEq => write!(f, "//="),
+            Self::In => write!(f, " in "),
             Self::Dot => write!(f, "."),
+            Self::Index => write!(f, "[]"),
             Self::And => write!(f, "&&"),
             Self::Or => write!(f, "||"),
             Self::Arrow => write!(f, "->"),
@@ -155,6 +328,11 @@ impl TryFrom<Token> for Operation {
             Token::SlashEq => Ok(Self::DivEq),
             Token::Percent => Ok(Self::Mod),
             Token::PercentEq => Ok(Self::ModEq),
+            Token::Caret => Ok(Self::Pow),
+            Token::CaretEq => Ok(Self::PowEq),
+            Token::SlashSlash => Ok(Self::FloorDiv),
+            Token::SlashSlashEq => Ok(Self::FloorDivEq),
+            Token::Ident(ident) if ident.as_ref() == "in" => Ok(Self::In),
             Token::Dot => Ok(Self::Dot),
             Token::And => Ok(Self::And),
             Token::Or => Ok(Self::Or),
@@ -1,20 +1,23 @@
 use std::{fmt::Display, rc::Rc};
 
-#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
-pub enum StringSegment {
+use super::Span;
+
+/// A piece of a string literal. Interpolations hold their tokens after lexing and their syntax after parsing.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum StringSegment<T> {
     String(Rc<str>),
-    Ident(Rc<str>),
-    ///  The symbol for the Cape Verdean escudo is placed in the decimal separator position, as in 2$50.
-    /// Developers from the Republic of Cape Verde can benefit from this syntax: `{e$code}`
-    Escudo(Rc<str>, Rc<str>),
+    /// `${expr}`, `£{expr}`, `¥{expr}`, `{expr}€`, `{expr}円`, or `{expr}₽`
+    ///
+    /// The symbol for the Cape Verdean escudo is placed in the decimal separator position, as in 2$50.
+    /// Developers from the Republic of Cape Verde can benefit from this syntax: `{e$code}` is the same as `${e.code}`
+    Interpolation(T),
 }
 
-impl Display for StringSegment {
+impl<T: Display> Display for StringSegment<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Ident(ident) => write!(f, "${{{ident}}}"),
+            Self::Interpolation(inner) => write!(f, "${{{inner}}}"),
             Self::String(str) => write!(f, "{str}"),
-            Self::Escudo(var, field) => write!(f, "{{{var}${field}}}"),
         }
     }
 }
@@ -22,9 +25,13 @@ impl Display for StringSegment {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Token {
     Ident(Rc<str>),
-    String(Vec<StringSegment>),
+    /// a numeric literal, spelled the way an `f64` displays so that it names the same thing however it was written
+    Number(Rc<str>),
+    String(Vec<StringSegment<Vec<(Self, Span)>>>),
     Space(u8),
     Equal(u8),
+    /// a line of five or more `=` that starts a new file, like `=====` or `===== add.db =====`, with the file's name
+    FileBreak(Option<Rc<str>>),
     Bang(u8),
     Question(u8),
     Plus,
@@ -39,6 +46,10 @@ pub enum Token {
     SlashEq,
     Percent,
     PercentEq,
+    SlashSlash,
+    SlashSlashEq,
+    Caret,
+    CaretEq,
     LCaret,
     LCaretEq,
     RCaret,
@@ -54,6 +65,7 @@ pub enum Token {
     Comma,
     Colon,
     Dot,
+    DotDotDot,
     And,
     Or,
 }
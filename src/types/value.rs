@@ -7,7 +7,10 @@ use std::{
     rc::Rc,
 };
 
-use super::{Pointer, Syntax};
+use super::{DreamBerdError, Param, Pointer, RcMut, SResult, State, Syntax};
+
+/// the most `undefined`s that setting an index past the end of an array can fill in
+pub const MAX_ARRAY_GAP: usize = 1 << 16;
 
 #[derive(PartialEq, Eq, Debug, Hash, Clone, Copy, PartialOrd, Ord)]
 pub enum Boolean {
@@ -42,26 +45,94 @@ pub enum Value {
     Boolean(Boolean),
     String(Rc<str>),
     Number(f64),
-    Object(BTreeMap<Value, Pointer>),
-    Function(Vec<Rc<str>>, Syntax),
-    Class(Vec<Syntax>),
+    Object(BTreeMap<Self, Pointer>),
+    /// a list of values, starting at index -1
+    Array(Vec<Pointer>),
+    /// a user-defined function. The body is shared between every copy of it, so it's only compiled once.
+    Function(Vec<Param>, Rc<Syntax>, Closure),
+    Class(Vec<Rc<Syntax>>),
     Keyword(Keyword),
 }
 
 impl Eq for Value {}
 
+/// Where a function came from.
+///
+/// That's the scope that it was made in, which it runs inside of, so it can still see the
+/// variables around it after that scope is done, and the name it was declared with, for stack traces. With
+/// `--dynamic-scope`, functions don't keep their scope, and run inside of whatever calls them instead.
+///
+/// Functions are still compared and hashed by their code alone.
+#[derive(Clone, Default)]
+pub struct Closure {
+    scope: Option<RcMut<State>>,
+    name: Option<Rc<str>>,
+}
+
+impl Closure {
+    pub fn capture(state: &RcMut<State>) -> Self {
+        if state.borrow().config.dynamic_scope {
+            return Self::default();
+        }
+        State::made_function(state);
+        Self {
+            scope: Some(state.clone()),
+            name: None,
+        }
+    }
+
+    #[must_use]
+    pub fn named(self, name: Rc<str>) -> Self {
+        Self {
+            name: Some(name),
+            ..self
+        }
+    }
+
+    pub fn scope(&self) -> Option<RcMut<State>> {
+        self.scope.clone()
+    }
+
+    /// the scope the function was made in, which is `None` with `--dynamic-scope`
+    pub const fn made_in(&self) -> Option<&RcMut<State>> {
+        self.scope.as_ref()
+    }
+
+    pub fn name(&self) -> Option<Rc<str>> {
+        self.name.clone()
+    }
+}
+
+impl PartialEq for Closure {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+// the scope can have the function in it, so printing it would never end
+impl Debug for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Closure")
+    }
+}
+
 impl Default for Value {
     fn default() -> Self {
         Self::Object(BTreeMap::new())
     }
 }
 
+// `Value`s are ordered by variant first, and only numbers, strings, booleans, keywords, and arrays are comparable within a variant
+#[allow(clippy::non_canonical_partial_ord_impl)]
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         match unsafe {
-            core::mem::transmute::<_, u64>(core::mem::discriminant(self)).cmp(
-                &core::mem::transmute::<_, u64>(core::mem::discriminant(other)),
-            )
+            core::mem::transmute::<core::mem::Discriminant<Self>, u64>(core::mem::discriminant(
+                self,
+            ))
+            .cmp(&core::mem::transmute::<core::mem::Discriminant<Self>, u64>(
+                core::mem::discriminant(other),
+            ))
         } {
             Ordering::Equal => {}
             other => return Some(other),
@@ -71,7 +142,9 @@ impl PartialOrd for Value {
             (Self::String(lhs), Self::String(rhs)) => lhs.partial_cmp(rhs),
             (Self::Boolean(lhs), Self::Boolean(rhs)) => lhs.partial_cmp(rhs),
             (Self::Keyword(lhs), Self::Keyword(rhs)) => lhs.partial_cmp(rhs),
-            _ => todo!(),
+            (Self::Array(lhs), Self::Array(rhs)) => lhs.partial_cmp(rhs),
+            // objects, functions, and classes don't have an order
+            _ => None,
         }
     }
 }
@@ -80,7 +153,7 @@ impl Ord for Value {
     fn cmp(&self, other: &Self) -> Ordering {
         if let Some(ord) = self.partial_cmp(other) {
             return ord;
-        };
+        }
         todo!()
     }
 }
@@ -89,6 +162,7 @@ impl Debug for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::String(str) => write!(f, "{str:?}"),
+            Self::Array(items) => write_array(f, items, |f, item| write!(f, "{item:?}")),
             this => write!(f, "{this}"),
         }
     }
@@ -111,8 +185,9 @@ impl Display for Value {
                     map.finish()
                 }
             }
-            Self::Function(args, body) => {
-                write!(f, "{args:?} -> {body}")
+            Self::Array(items) => write_array(f, items, |f, item| write!(f, "{item}")),
+            Self::Function(args, body, _) => {
+                write!(f, "({}) -> {body}", Param::list(args))
             }
             Self::Class(syn) => {
                 write!(f, "class {{{syn:?}}}")
@@ -123,6 +198,22 @@ impl Display for Value {
     }
 }
 
+/// write `[a, b, c]`, using `write_item` for each of the values
+fn write_array(
+    f: &mut std::fmt::Formatter<'_>,
+    items: &[Pointer],
+    write_item: impl Fn(&mut std::fmt::Formatter<'_>, &Value) -> std::fmt::Result,
+) -> std::fmt::Result {
+    write!(f, "[")?;
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+            write!(f, ", ")?;
+        }
+        item.with_ref(|item| write_item(f, item))?;
+    }
+    write!(f, "]")
+}
+
 impl Hash for Value {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         core::mem::discriminant(self).hash(state);
@@ -137,7 +228,8 @@ impl Hash for Value {
                     (k, v).hash(state);
                 }
             }
-            Self::Function(inputs, content) => (inputs, content).hash(state),
+            Self::Array(items) => items.hash(state),
+            Self::Function(inputs, content, _) => (inputs, content).hash(state),
             Self::Class(body) => body.hash(state),
             Self::Keyword(keyword) => keyword.hash(state),
         }
@@ -203,15 +295,19 @@ impl Mul for Value {
                 }
                 Self::String(str_buf.into())
             }
-            (Self::Function(lhs_args, lhs), Self::Function(rhs_args, rhs))
+            // the composed function runs where `rhs` was made
+            (Self::Function(lhs_args, lhs, _), Self::Function(rhs_args, rhs, closure))
                 if lhs_args.len() == 1 =>
             {
+                let span = lhs.span().to(rhs.span());
                 Self::Function(
                     rhs_args,
-                    Syntax::UnaryOperation(
-                        super::UnaryOperation::Call(vec![rhs]),
-                        Box::new(Syntax::Function(lhs_args, Box::new(lhs))),
-                    ),
+                    Rc::new(Syntax::UnaryOperation(
+                        super::UnaryOperation::Call(Rc::new([Rc::unwrap_or_clone(rhs)])),
+                        Box::new(Syntax::Function(lhs_args, lhs, span)),
+                        span,
+                    )),
+                    closure,
                 )
             }
             _ => Self::default(),
@@ -288,6 +384,52 @@ impl BitOr for Value {
 }
 
 impl Value {
+    /// the value of a number or a boolean in arithmetic, where `maybe` is halfway between `false` and `true`
+    const fn as_number(&self) -> Option<f64> {
+        match self {
+            Self::Number(num) => Some(*num),
+            Self::Boolean(Boolean::False) => Some(0.0),
+            Self::Boolean(Boolean::Maybe) => Some(0.5),
+            Self::Boolean(Boolean::True) => Some(1.0),
+            _ => None,
+        }
+    }
+
+    /// `^` raises numbers to a power. Between two booleans, it's exclusive or, and it's `maybe` if either of them is.
+    pub fn pow(&self, rhs: &Self) -> Self {
+        match (self, rhs) {
+            (Self::Boolean(Boolean::Maybe), Self::Boolean(_))
+            | (Self::Boolean(_), Self::Boolean(Boolean::Maybe)) => Self::Boolean(Boolean::Maybe),
+            (Self::Boolean(lhs), Self::Boolean(rhs)) => Self::from(lhs != rhs),
+            _ => match (self.as_number(), rhs.as_number()) {
+                (Some(lhs), Some(rhs)) => Self::Number(lhs.powf(rhs)),
+                _ => Self::default(),
+            },
+        }
+    }
+
+    /// `//` divides and rounds down. Just like with `/`, dividing by zero is undefined.
+    pub fn floor_div(&self, rhs: &Self) -> Self {
+        match (self.as_number(), rhs.as_number()) {
+            (Some(lhs), Some(rhs)) if rhs != 0.0 => Self::Number((lhs / rhs).floor()),
+            _ => Self::default(),
+        }
+    }
+
+    /// `in` checks whether this is a key of an object, part of a string, or an element of an array
+    pub fn is_in(&self, container: &Self) -> Self {
+        match (self, container) {
+            // only these can be compared with object keys
+            (
+                Self::String(_) | Self::Number(_) | Self::Boolean(_) | Self::Keyword(_),
+                Self::Object(obj),
+            ) => Self::from(obj.contains_key(self)),
+            (_, Self::String(str)) => Self::from(str.contains(&*self.to_string())),
+            (_, Self::Array(items)) => Self::from(items.iter().any(|item| *item == *self)),
+            _ => Self::from(false),
+        }
+    }
+
     pub fn eq(&self, rhs: &Self, precision: u8) -> Self {
         if precision <= 2 && self.bool() == Boolean::False && rhs.bool() == Boolean::False {
             return Self::from(true);
@@ -316,16 +458,22 @@ impl Value {
             (Self::String(ref str), &Self::Number(num))
             | (&Self::Number(num), Self::String(ref str)) => {
                 let Ok(str_parse) = str.parse::<f64>() else {
-                    return Self::from(false)
+                    return Self::from(false);
                 };
                 Self::from(
                     num == str_parse || (precision == 1 && (num / str_parse).ln().abs() < 0.1),
                 )
             }
+            (Self::Array(lhs), Self::Array(rhs)) => Self::from(
+                lhs.len() == rhs.len()
+                    && lhs.iter().zip(rhs).all(|(lhs, rhs)| {
+                        lhs.with_refs(rhs, |lhs, rhs| lhs.eq(rhs, precision)) != Self::from(false)
+                    }),
+            ),
             (Self::Object(lhs), Self::Object(rhs)) => Self::from(
                 !lhs.iter().any(|(k, v)| {
                     rhs.get(k)
-                        .map_or(true, |r| r.eq(v, precision) == Self::from(false))
+                        .is_none_or(|r| r.eq(v, precision) == Self::from(false))
                 }) && !rhs.iter().any(|(k, _)| lhs.get(k).is_none()),
             ),
             _ => Self::from(false),
@@ -358,13 +506,68 @@ impl Value {
                     Boolean::True
                 }
             }
+            Self::Array(items) => {
+                if items.is_empty() {
+                    Boolean::False
+                } else {
+                    Boolean::True
+                }
+            }
             _ => Boolean::Maybe,
         }
     }
 
+    /// Put `item` at `idx` in an array that starts at -1. Whole indexes replace an element, filling in any gap
+    /// with `undefined`, and fractional indexes insert the item between two elements, so `0.5` goes between `0` and `1`.
+    /// Indexes past the end can only leave a gap of [`MAX_ARRAY_GAP`] elements.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    pub fn set_element(items: &mut Vec<Pointer>, idx: f64, item: Pointer) -> SResult<()> {
+        if !idx.is_finite() || idx.ceil() < -1.0 {
+            return Err(DreamBerdError::runtime(format!(
+                "Arrays start at -1, so there's nowhere to put index {idx}"
+            )));
+        }
+        if idx.ceil() >= (items.len() + MAX_ARRAY_GAP) as f64 {
+            return Err(DreamBerdError::runtime(format!(
+                "Index {idx} is too far past the end of an array with {} elements; at most {MAX_ARRAY_GAP} \
+                 `undefined`s can be put in between",
+                items.len()
+            )));
+        }
+        let position = (idx.ceil() + 1.0) as usize;
+        if idx.fract() != 0.0 {
+            items.insert(position.min(items.len()), item);
+        } else if let Some(slot) = items.get_mut(position) {
+            *slot = item;
+        } else {
+            items.resize(position, Pointer::from(Self::empty_object()));
+            items.push(item);
+        }
+        Ok(())
+    }
+
     pub const fn empty_object() -> Self {
         Self::Object(BTreeMap::new())
     }
+
+    /// the name of this value's type, for error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Boolean(_) => "Boolean",
+            Self::String(_) => "String",
+            Self::Number(_) => "Number",
+            Self::Object(obj) if obj.is_empty() => "undefined",
+            Self::Object(_) => "Object",
+            Self::Array(_) => "Array",
+            Self::Function(..) => "Fn",
+            Self::Class(_) => "Class",
+            Self::Keyword(_) => "Keyword",
+        }
+    }
 }
 
 impl From<f64> for Value {
@@ -407,9 +610,14 @@ pub enum Keyword {
     Forget,
     Function,
     If,
+    Len,
     Next,
     New,
+    Pop,
     Previous,
+    Push,
+    Return,
+    Stack,
     Var,
     When,
 }
@@ -425,9 +633,14 @@ impl Display for Keyword {
             Self::Forget => write!(f, "forget"),
             Self::Function => write!(f, "function"),
             Self::If => write!(f, "if"),
+            Self::Len => write!(f, "len"),
             Self::Next => write!(f, "next"),
             Self::New => write!(f, "new"),
+            Self::Pop => write!(f, "pop"),
             Self::Previous => write!(f, "previous"),
+            Self::Push => write!(f, "push"),
+            Self::Return => write!(f, "return"),
+            Self::Stack => write!(f, "stack"),
             Self::Var => write!(f, "var"),
             Self::When => write!(f, "when"),
         }
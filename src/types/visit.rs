@@ -0,0 +1,234 @@
+//! Walking over syntax trees.
+//!
+//! Each trait has a method for every kind of node that does nothing but visit
+//! the node's children, so an implementation only has to override the nodes it cares about, and can call the
+//! matching `walk` function to keep going into the children of those.
+
+use std::rc::Rc;
+
+use super::{Param, Span, StringSegment, Syntax, UnaryOperation};
+
+/// Look at every node of a syntax tree, parents before their children, in the order they're written
+pub trait Visitor {
+    fn visit_syntax(&mut self, syn: &Syntax) {
+        walk_syntax(self, syn);
+    }
+
+    /// a variable being used, not one being declared
+    fn visit_ident(&mut self, _name: &Rc<str>, _span: Span) {}
+
+    fn visit_param(&mut self, param: &Param) {
+        walk_param(self, param);
+    }
+}
+
+/// visit the children of `syn`
+pub fn walk_syntax<V: Visitor + ?Sized>(visitor: &mut V, syn: &Syntax) {
+    match syn {
+        Syntax::Ident(name, span) => visitor.visit_ident(name, *span),
+        Syntax::UnaryOperation(UnaryOperation::Call(args), func, _) => {
+            visitor.visit_syntax(func);
+            for arg in args.iter() {
+                visitor.visit_syntax(arg);
+            }
+        }
+        Syntax::Declare(.., value, _)
+        | Syntax::Destructure(.., value, _)
+        | Syntax::UnaryOperation(_, value, _) => visitor.visit_syntax(value),
+        Syntax::Function(params, body, _) => {
+            for param in params {
+                visitor.visit_param(param);
+            }
+            visitor.visit_syntax(body);
+        }
+        Syntax::Operation(lhs, _, rhs, _) => {
+            visitor.visit_syntax(lhs);
+            visitor.visit_syntax(rhs);
+        }
+        Syntax::String(segments, _) => {
+            for segment in segments {
+                if let StringSegment::Interpolation(inner) = segment {
+                    visitor.visit_syntax(inner);
+                }
+            }
+        }
+        Syntax::Block(items, _) | Syntax::Array(items, _) => {
+            for item in items {
+                visitor.visit_syntax(item);
+            }
+        }
+        Syntax::Statement(_, inner, ..)
+        | Syntax::Annotated(inner, ..)
+        | Syntax::File(_, inner, _)
+        | Syntax::Export(_, inner, _) => visitor.visit_syntax(inner),
+        Syntax::Error(_) => {}
+    }
+}
+
+/// visit the default value of `param`, if it has one
+pub fn walk_param<V: Visitor + ?Sized>(visitor: &mut V, param: &Param) {
+    if let Some(default) = &param.default {
+        visitor.visit_syntax(default);
+    }
+}
+
+/// Collects the name of every variable that's used, in the order they're used
+#[derive(Default)]
+pub struct Idents(pub Vec<Rc<str>>);
+
+impl Visitor for Idents {
+    fn visit_ident(&mut self, name: &Rc<str>, _: Span) {
+        self.0.push(name.clone());
+    }
+}
+
+/// Change a syntax tree in place, with the same order as [`Visitor`]
+pub trait VisitorMut {
+    fn visit_syntax_mut(&mut self, syn: &mut Syntax) {
+        walk_syntax_mut(self, syn);
+    }
+
+    fn visit_ident_mut(&mut self, _name: &mut Rc<str>, _span: Span) {}
+
+    fn visit_param_mut(&mut self, param: &mut Param) {
+        walk_param_mut(self, param);
+    }
+}
+
+/// visit the children of `syn`, and let them be changed
+pub fn walk_syntax_mut<V: VisitorMut + ?Sized>(visitor: &mut V, syn: &mut Syntax) {
+    match syn {
+        Syntax::Ident(name, span) => visitor.visit_ident_mut(name, *span),
+        Syntax::UnaryOperation(UnaryOperation::Call(args), func, _) => {
+            visitor.visit_syntax_mut(func);
+            // shared syntax is copied before it's changed
+            for arg in Rc::make_mut(args) {
+                visitor.visit_syntax_mut(arg);
+            }
+        }
+        Syntax::Declare(.., value, _)
+        | Syntax::Destructure(.., value, _)
+        | Syntax::UnaryOperation(_, value, _) => visitor.visit_syntax_mut(value),
+        Syntax::Function(params, body, _) => {
+            for param in params {
+                visitor.visit_param_mut(param);
+            }
+            visitor.visit_syntax_mut(Rc::make_mut(body));
+        }
+        Syntax::Operation(lhs, _, rhs, _) => {
+            visitor.visit_syntax_mut(lhs);
+            visitor.visit_syntax_mut(rhs);
+        }
+        Syntax::String(segments, _) => {
+            for segment in segments {
+                if let StringSegment::Interpolation(inner) = segment {
+                    visitor.visit_syntax_mut(inner);
+                }
+            }
+        }
+        Syntax::Block(items, _) | Syntax::Array(items, _) => {
+            for item in items {
+                visitor.visit_syntax_mut(item);
+            }
+        }
+        Syntax::Statement(_, inner, ..)
+        | Syntax::Annotated(inner, ..)
+        | Syntax::File(_, inner, _)
+        | Syntax::Export(_, inner, _) => visitor.visit_syntax_mut(inner),
+        Syntax::Error(_) => {}
+    }
+}
+
+/// visit the default value of `param`, if it has one, and let it be changed
+pub fn walk_param_mut<V: VisitorMut + ?Sized>(visitor: &mut V, param: &mut Param) {
+    if let Some(default) = &mut param.default {
+        visitor.visit_syntax_mut(Rc::make_mut(default));
+    }
+}
+
+/// Build a new syntax tree out of an old one, children first
+pub trait Fold {
+    fn fold_syntax(&mut self, syn: Syntax) -> Syntax {
+        fold_children(self, syn)
+    }
+
+    fn fold_param(&mut self, param: Param) -> Param {
+        Param {
+            default: param
+                .default
+                .map(|default| Rc::new(self.fold_syntax(Rc::unwrap_or_clone(default)))),
+            ..param
+        }
+    }
+}
+
+/// fold each of the children of `syn`, and put them back together the same way
+pub fn fold_children<F: Fold + ?Sized>(folder: &mut F, syn: Syntax) -> Syntax {
+    let mut fold = |syn: Box<Syntax>| Box::new(folder.fold_syntax(*syn));
+    match syn {
+        Syntax::Declare(typ, name, lifetime, annotation, value, span) => {
+            Syntax::Declare(typ, name, lifetime, annotation, fold(value), span)
+        }
+        Syntax::Destructure(typ, fields, lifetime, value, span) => {
+            Syntax::Destructure(typ, fields, lifetime, fold(value), span)
+        }
+        Syntax::Operation(lhs, op, rhs, span) => {
+            let lhs = fold(lhs);
+            Syntax::Operation(lhs, op, fold(rhs), span)
+        }
+        Syntax::Statement(is_debug, inner, level, span) => {
+            Syntax::Statement(is_debug, fold(inner), level, span)
+        }
+        Syntax::Annotated(inner, annotation, span) => {
+            Syntax::Annotated(fold(inner), annotation, span)
+        }
+        Syntax::File(name, body, span) => Syntax::File(name, fold(body), span),
+        Syntax::Export(name, target, span) => Syntax::Export(name, fold(target), span),
+        Syntax::UnaryOperation(UnaryOperation::Call(args), func, span) => {
+            let func = folder.fold_syntax(*func);
+            let args = args
+                .iter()
+                .map(|arg| folder.fold_syntax(arg.clone()))
+                .collect();
+            Syntax::UnaryOperation(UnaryOperation::Call(args), Box::new(func), span)
+        }
+        Syntax::UnaryOperation(unary, operand, span) => {
+            Syntax::UnaryOperation(unary, fold(operand), span)
+        }
+        Syntax::Function(params, body, span) => {
+            let params = params
+                .into_iter()
+                .map(|param| folder.fold_param(param))
+                .collect();
+            let body = folder.fold_syntax(Rc::unwrap_or_clone(body));
+            Syntax::Function(params, Rc::new(body), span)
+        }
+        Syntax::String(segments, span) => Syntax::String(
+            segments
+                .into_iter()
+                .map(|segment| match segment {
+                    StringSegment::Interpolation(inner) => {
+                        StringSegment::Interpolation(folder.fold_syntax(inner))
+                    }
+                    StringSegment::String(str) => StringSegment::String(str),
+                })
+                .collect(),
+            span,
+        ),
+        Syntax::Block(items, span) => Syntax::Block(
+            items
+                .into_iter()
+                .map(|item| folder.fold_syntax(item))
+                .collect(),
+            span,
+        ),
+        Syntax::Array(items, span) => Syntax::Array(
+            items
+                .into_iter()
+                .map(|item| folder.fold_syntax(item))
+                .collect(),
+            span,
+        ),
+        basic @ (Syntax::Ident(..) | Syntax::Error(_)) => basic,
+    }
+}
@@ -0,0 +1,269 @@
+use std::{cell::OnceCell, rc::Rc};
+
+use crate::{
+    interpreter::{misplaced_rest, unparsed},
+    types::prelude::*,
+};
+
+/// One instruction. Each one takes its operands off the top of the stack and puts its result back on it.
+#[derive(Debug)]
+pub enum Op {
+    /// the value of a variable
+    Get(Rc<str>),
+    Undefined,
+    /// throw away the value on top of the stack
+    Pop,
+    /// a statement that ends with `!`; `bare` is whether it's only a name
+    EndStatement {
+        bare: bool,
+    },
+    /// `???` prints the syntax of a statement before running it
+    PrintSyntax(Box<Syntax>),
+    /// print the value on top of the stack, and leave it there
+    Print(u8),
+    Negate,
+    /// `++` or `--`
+    Step {
+        increment: bool,
+    },
+    Binary(Operation, Span),
+    /// `lhs.name`, which finds or makes a field if `lhs` is an object
+    Dot(Rc<str>, Span),
+    /// `target: annotation = value`, with the value on top of the stack
+    CheckType(Rc<str>, Box<Type>, Span),
+    /// `target[index] = value`
+    AssignIndex(Box<Syntax>, Span),
+    /// go into a block, which gets its own scope
+    EnterScope,
+    ExitScope,
+    /// the end of a statement in a block, for lifetimes and the budget
+    Tick(Span),
+    Declare(VarType, Rc<str>, Lifetime, Option<Box<Type>>, Span),
+    Destructure(VarType, Rc<[(Rc<str>, Rc<str>)]>, Lifetime),
+    /// put a string together from its pieces; each `None` takes a value off the stack
+    Format(Vec<Option<Rc<str>>>),
+    /// make an array out of this many values
+    Array(usize),
+    Function(Vec<Param>, Rc<Syntax>),
+    /// Call the function on top of the stack. The arguments aren't run yet, because the function decides that.
+    Call(Box<Call>),
+    File(Option<Rc<str>>, Chunk),
+    /// export a variable to the file on top of the stack
+    Export(Rc<str>),
+    Fail(Box<DreamBerdError>),
+}
+
+#[derive(Debug)]
+pub struct Call {
+    pub args: Vec<Arg>,
+    pub span: Span,
+    /// the call is the last thing that happens, so its value is the value of everything that's running
+    pub tail: bool,
+    /// the call is a statement, so whatever it gives back is thrown away
+    pub discard: bool,
+}
+
+/// An argument of a call, which is only compiled once it's run, since the function might not run it at all
+#[derive(Debug)]
+pub struct Arg {
+    /// every argument of the call, shared with the syntax tree
+    all: Rc<[Syntax]>,
+    idx: usize,
+    code: OnceCell<Chunk>,
+    /// just the value of a named argument like `b = 3`
+    named: OnceCell<Chunk>,
+}
+
+/// A compiled piece of code, which leaves one value on the stack
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub ops: Vec<Op>,
+}
+
+/// Compile `syn`. Calls in tail position are marked, so that they can hand back their call instead of making
+/// it when the chunk is run as the body of a function.
+pub fn compile(syn: &Syntax) -> Chunk {
+    let mut chunk = Chunk::default();
+    chunk.push(syn, true);
+    chunk
+}
+
+impl Chunk {
+    #[allow(clippy::too_many_lines)]
+    fn push(&mut self, syn: &Syntax, tail: bool) {
+        match syn {
+            Syntax::Statement(false, content, ..)
+                if tail
+                    && matches!(
+                        **content,
+                        Syntax::UnaryOperation(UnaryOperation::Call(_), ..)
+                    ) =>
+            {
+                self.push(content, true);
+                if let Some(Op::Call(call)) = self.ops.last_mut() {
+                    call.discard = true;
+                }
+            }
+            Syntax::Statement(false, content, ..) => {
+                self.push(content, false);
+                self.ops.push(Op::EndStatement {
+                    bare: matches!(**content, Syntax::Ident(..)),
+                });
+            }
+            Syntax::Statement(true, content, level, _) => {
+                if *level >= 3 {
+                    self.ops.push(Op::PrintSyntax(content.clone()));
+                }
+                self.push(content, false);
+                self.ops.push(Op::Print(*level));
+            }
+            Syntax::UnaryOperation(UnaryOperation::Negate, content, _) => {
+                self.push(content, false);
+                self.ops.push(Op::Negate);
+            }
+            Syntax::Operation(lhs, op, rhs, span) => self.operation(lhs, *op, rhs, *span),
+            Syntax::Annotated(inner, ..) => self.push(inner, tail),
+            Syntax::UnaryOperation(
+                unary @ (UnaryOperation::Increment | UnaryOperation::Decrement),
+                operand,
+                _,
+            ) => {
+                self.push(operand, false);
+                self.ops.push(Op::Step {
+                    increment: *unary == UnaryOperation::Increment,
+                });
+            }
+            Syntax::Block(statements, _) => {
+                self.ops.push(Op::EnterScope);
+                let Some((last, rest)) = statements.split_last() else {
+                    self.ops.extend([Op::Undefined, Op::ExitScope]);
+                    return;
+                };
+                for syn in rest {
+                    self.push(syn, false);
+                    self.ops.extend([Op::Pop, Op::Tick(syn.span())]);
+                }
+                self.push(last, tail);
+                self.ops.push(Op::ExitScope);
+            }
+            Syntax::Declare(var_type, ident, lifetime, annotation, value, span) => {
+                self.push(value, false);
+                self.ops.push(Op::Declare(
+                    *var_type,
+                    ident.clone(),
+                    *lifetime,
+                    annotation.clone().map(Box::new),
+                    *span,
+                ));
+            }
+            Syntax::Destructure(var_type, fields, lifetime, value, _) => {
+                self.push(value, false);
+                self.ops.push(Op::Destructure(
+                    *var_type,
+                    fields.as_slice().into(),
+                    *lifetime,
+                ));
+            }
+            Syntax::File(name, body, _) => {
+                self.ops.push(Op::File(name.clone(), compile(body)));
+            }
+            Syntax::Export(name, target, _) => {
+                self.push(target, false);
+                self.ops.push(Op::Export(name.clone()));
+            }
+            Syntax::String(segments, _) => {
+                let pieces = segments
+                    .iter()
+                    .map(|segment| match segment {
+                        StringSegment::String(str) => Some(str.clone()),
+                        StringSegment::Interpolation(inner) => {
+                            self.push(inner, false);
+                            None
+                        }
+                    })
+                    .collect();
+                self.ops.push(Op::Format(pieces));
+            }
+            Syntax::Array(items, _) => {
+                for item in items {
+                    self.push(item, false);
+                }
+                self.ops.push(Op::Array(items.len()));
+            }
+            Syntax::UnaryOperation(UnaryOperation::Call(args), func, span) => {
+                self.push(func, false);
+                self.ops.push(Op::Call(Box::new(Call {
+                    args: Arg::all(args),
+                    span: *span,
+                    tail,
+                    discard: false,
+                })));
+            }
+            Syntax::UnaryOperation(UnaryOperation::Rest, _, span) => {
+                self.ops.push(Op::Fail(Box::new(misplaced_rest(*span))));
+            }
+            Syntax::Ident(ident, _) => self.ops.push(Op::Get(ident.clone())),
+            Syntax::Error(span) => self.ops.push(Op::Fail(Box::new(unparsed(*span)))),
+            Syntax::Function(params, body, _) => {
+                self.ops.push(Op::Function(params.clone(), body.clone()));
+            }
+        }
+    }
+
+    fn operation(&mut self, lhs: &Syntax, op: Operation, rhs: &Syntax, span: Span) {
+        match (lhs, op, rhs) {
+            (Syntax::Operation(target, Operation::Index, index, _), Operation::Equal(1), _) => {
+                self.push(target, false);
+                self.push(index, false);
+                self.push(rhs, false);
+                self.ops.push(Op::AssignIndex(target.clone(), span));
+            }
+            (_, Operation::Dot, Syntax::Ident(ident, _)) => {
+                self.push(lhs, false);
+                self.ops.push(Op::Dot(ident.clone(), span));
+            }
+            _ => {
+                self.push(lhs, false);
+                self.push(rhs, false);
+                if let (Syntax::Annotated(target, annotation, _), Operation::Equal(1)) = (lhs, op) {
+                    self.ops.push(Op::CheckType(
+                        target.to_string().into(),
+                        Box::new(annotation.clone()),
+                        span,
+                    ));
+                }
+                self.ops.push(Op::Binary(op, span));
+            }
+        }
+    }
+}
+
+impl Arg {
+    /// an argument for each of the arguments of a call
+    fn all(args: &Rc<[Syntax]>) -> Vec<Self> {
+        (0..args.len())
+            .map(|idx| Self {
+                all: args.clone(),
+                idx,
+                code: OnceCell::new(),
+                named: OnceCell::new(),
+            })
+            .collect()
+    }
+
+    pub fn syntax(&self) -> &Syntax {
+        &self.all[self.idx]
+    }
+
+    pub fn code(&self) -> &Chunk {
+        self.code.get_or_init(|| compile(self.syntax()))
+    }
+
+    /// the code for just the value of `name = value`
+    pub fn named(&self) -> &Chunk {
+        self.named.get_or_init(|| match self.syntax() {
+            Syntax::Operation(_, Operation::Equal(1), value, _) => compile(value),
+            other => compile(other),
+        })
+    }
+}
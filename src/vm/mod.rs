@@ -0,0 +1,269 @@
+//! Runs code by compiling it to a list of instructions first, instead of walking the syntax tree. Calls work the
+//! same way as in the tree-walking interpreter, because what a call does depends on what it calls: `if` only runs
+//! one of its arguments, and `when` doesn't run them at all. So arguments are compiled on their own, and each
+//! function decides when to run them.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    interpreter::{
+        assign_index, call_value, check_type, declare, destructure, element, end_file,
+        end_statement, export, field, operate, print_value, step, tail_call, tick, Evaluator, Flow,
+        Tail,
+    },
+    types::prelude::*,
+};
+
+use compiler::{compile, Arg, Chunk, Op};
+
+mod compiler;
+
+#[derive(Default)]
+pub struct Vm {
+    /// the code that's been compiled, like the bodies of functions that have been called
+    cache: RefCell<Cache>,
+}
+
+/// the most compiled code to keep before throwing away what's left over from syntax that's been dropped
+const MIN_CACHE_LIMIT: usize = 64;
+
+/// Compiled code, by the syntax it was compiled from
+#[derive(Default)]
+struct Cache {
+    chunks: HashMap<Source, Rc<Chunk>>,
+    /// how big `chunks` can get before it's cleaned up
+    limit: usize,
+}
+
+/// Syntax that's compared by which `Rc` it is, instead of by what's in it. It's only a weak pointer, so code
+/// can still be dropped after it's compiled, but that's enough to keep anything else from taking its place.
+struct Source(Weak<Syntax>);
+
+impl PartialEq for Source {
+    fn eq(&self, other: &Self) -> bool {
+        Weak::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Source {}
+
+impl Hash for Source {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_ptr().hash(state);
+    }
+}
+
+impl Cache {
+    /// get the compiled code for `syn`, compiling it if it hasn't been already
+    fn chunk(&mut self, syn: &Rc<Syntax>) -> Rc<Chunk> {
+        let source = Source(Rc::downgrade(syn));
+        if let Some(chunk) = self.chunks.get(&source) {
+            return chunk.clone();
+        }
+        if self.chunks.len() >= self.limit {
+            self.chunks.retain(|source, _| source.0.strong_count() > 0);
+            self.limit = (self.chunks.len() * 2).max(MIN_CACHE_LIMIT);
+        }
+        let chunk = Rc::new(compile(syn));
+        self.chunks.insert(source, chunk.clone());
+        chunk
+    }
+}
+
+impl Vm {
+    /// compile and run some code
+    pub fn run(&self, src: &Syntax, state: RcMut<State>) -> Flow<Pointer> {
+        self.value(&compile(src), state)
+    }
+
+    fn value(&self, chunk: &Chunk, state: RcMut<State>) -> Flow<Pointer> {
+        match self.exec(chunk, state, false)? {
+            Tail::Done(val) => Ok(val),
+            Tail::Call(_) => unreachable!("calls are only handed back in tail position"),
+        }
+    }
+
+    /// Run a chunk. If `tail` is true, a call at the very end is handed back instead of being made.
+    #[allow(clippy::too_many_lines)]
+    fn exec(&self, chunk: &Chunk, state: RcMut<State>, tail: bool) -> Flow<Tail> {
+        let mut scopes = vec![state];
+        let mut stack: Vec<Pointer> = Vec::new();
+        for op in &chunk.ops {
+            let state = &scopes
+                .last()
+                .expect("the outermost scope is never exited")
+                .clone();
+            let undefined = || state.borrow().undefined.clone();
+            let result = match op {
+                Op::Get(name) => state.borrow_mut().get(name.clone()),
+                Op::Undefined => undefined(),
+                Op::Pop => {
+                    stack.pop();
+                    continue;
+                }
+                Op::EndStatement { bare } => end_statement(*bare, &pop(&mut stack), state)?,
+                Op::PrintSyntax(content) => {
+                    println!("{content:?}");
+                    continue;
+                }
+                Op::Print(level) => {
+                    print_value(stack.last().expect("`?` has a value to print"), *level);
+                    continue;
+                }
+                Op::Negate => -pop(&mut stack),
+                Op::Step { increment } => step(pop(&mut stack), *increment),
+                Op::Binary(op, span) => {
+                    let rhs = pop(&mut stack);
+                    let lhs = pop(&mut stack);
+                    operate(self, lhs, *op, rhs, *span)?
+                }
+                Op::Dot(name, span) => {
+                    let lhs = pop(&mut stack);
+                    if let Some(field) = field(&lhs, name, state) {
+                        field
+                    } else {
+                        let rhs = state.borrow_mut().get(name.clone());
+                        operate(self, lhs, Operation::Dot, rhs, *span)?
+                    }
+                }
+                Op::CheckType(name, annotation, span) => {
+                    let value = stack
+                        .last()
+                        .expect("the value being assigned is on the stack");
+                    check_type(name, Some(annotation), value, *span, &state.borrow().config)?;
+                    continue;
+                }
+                Op::AssignIndex(target, span) => {
+                    let rhs = pop(&mut stack);
+                    let index = pop(&mut stack);
+                    let target_eval = pop(&mut stack);
+                    assign_index(self, target, &target_eval, &index, rhs, *span)?
+                }
+                Op::EnterScope => {
+                    let inner = rc_mut_new(State::from_parent(state.clone()));
+                    scopes.push(inner);
+                    continue;
+                }
+                Op::ExitScope => {
+                    scopes.pop();
+                    continue;
+                }
+                Op::Tick(span) => {
+                    tick(state, *span)?;
+                    continue;
+                }
+                Op::Declare(var_type, ident, lifetime, annotation, span) => {
+                    let value = pop(&mut stack);
+                    declare(
+                        *var_type,
+                        ident,
+                        *lifetime,
+                        annotation.as_deref(),
+                        &value,
+                        *span,
+                        state,
+                    )?;
+                    undefined()
+                }
+                Op::Destructure(var_type, fields, lifetime) => {
+                    let value = pop(&mut stack);
+                    destructure(*var_type, fields, *lifetime, &value, state);
+                    undefined()
+                }
+                Op::Format(pieces) => {
+                    let count = pieces.iter().filter(|piece| piece.is_none()).count();
+                    let mut values = stack.split_off(stack.len() - count).into_iter();
+                    let mut string_buf = String::new();
+                    for piece in pieces {
+                        match piece {
+                            Some(str) => string_buf.push_str(str),
+                            None => string_buf
+                                .push_str(&values.next().expect("one value per piece").to_string()),
+                        }
+                    }
+                    Pointer::from(string_buf.as_ref())
+                }
+                Op::Array(len) => {
+                    let items = stack.split_off(stack.len() - len);
+                    Pointer::from(Value::Array(items.iter().map(element).collect()))
+                }
+                Op::Function(params, body) => Pointer::from(Value::Function(
+                    params.clone(),
+                    body.clone(),
+                    Closure::capture(state),
+                )),
+                Op::Call(call) => {
+                    let func = pop(&mut stack);
+                    if tail && call.tail {
+                        let next = tail_call(self, &func, &call.args, call.span, state)?;
+                        return Ok(if call.discard {
+                            next.discard(state)
+                        } else {
+                            next
+                        });
+                    }
+                    let value = call_value(self, &func, &call.args, call.span, state.clone())?;
+                    if call.discard {
+                        undefined()
+                    } else {
+                        value
+                    }
+                }
+                Op::File(name, body) => {
+                    let file_state = rc_mut_new(state.borrow().for_file(name.as_deref()));
+                    end_file(self.value(body, file_state))?
+                }
+                Op::Export(name) => {
+                    let file = pop(&mut stack);
+                    export(name, &file, state);
+                    undefined()
+                }
+                Op::Fail(err) => return Err((**err).clone().into()),
+            };
+            stack.push(result);
+        }
+        Ok(Tail::Done(pop(&mut stack)))
+    }
+}
+
+/// the value that the last instruction left on the stack
+fn pop(stack: &mut Vec<Pointer>) -> Pointer {
+    stack
+        .pop()
+        .expect("every instruction leaves its value on the stack")
+}
+
+impl Evaluator for Vm {
+    type Arg = Arg;
+
+    fn syntax(arg: &Arg) -> &Syntax {
+        arg.syntax()
+    }
+
+    fn eval(&self, arg: &Arg, state: RcMut<State>) -> Flow<Pointer> {
+        self.value(arg.code(), state)
+    }
+
+    fn eval_named(&self, arg: &Arg, state: RcMut<State>) -> Flow<Pointer> {
+        self.value(arg.named(), state)
+    }
+
+    fn eval_tail(&self, arg: &Arg, state: RcMut<State>) -> Flow<Tail> {
+        self.exec(arg.code(), state, true)
+    }
+
+    fn run(&self, syn: &Rc<Syntax>, state: RcMut<State>) -> Flow<Pointer> {
+        let chunk = self.cache.borrow_mut().chunk(syn);
+        self.value(&chunk, state)
+    }
+
+    fn run_body(&self, body: &Rc<Syntax>, state: RcMut<State>) -> Flow<Tail> {
+        let chunk = self.cache.borrow_mut().chunk(body);
+        self.exec(&chunk, state, true)
+    }
+}